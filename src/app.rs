@@ -1,7 +1,24 @@
-use crate::screen::{Rx, Tx};
-use crossterm::event::{Event, KeyCode, KeyEvent};
-use serialport::SerialPort;
-use std::io;
+use crate::decode::{ControlStyle, Encoding, HexEscape};
+use crate::escape;
+use crate::events::{Event, PortIo};
+use crate::keymap::{Action, Keymap};
+use crate::keys;
+use crate::line_edit::{LineEditor, SendMode};
+use crate::line_ending::{LineEnding, RxNewline};
+use crate::logger::Direction;
+use crate::macros::{self, Macro, Macros};
+use crate::paths;
+use crate::picker::{Pick, PortPicker};
+use crate::screen::{Display, DumpWidth, Search};
+use crate::send_file::{self, FileSend};
+use crate::serial::{self, LineConfig, ModemStatus, PortId};
+use crate::session::{self, Session};
+use crate::timeline::Timeline;
+use crate::transfer::{self, Protocol, Request};
+use crate::vt::Vt;
+use crossterm::event::{self as input, KeyCode, KeyEvent, KeyModifiers};
+use std::io::{self, Write};
+use std::sync::mpsc::Sender;
 use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy)]
@@ -19,9 +36,27 @@ impl Control {
 pub enum Mode {
     Normal,
     Insert,
-    Config,
+    Config(ConfigMenu),
     WannaQuit,
-    BaudInput(String),
+    BaudInput(BaudForm),
+    /// typing a search query for the focused pane
+    Search(String),
+    /// keys go straight to the port and RX is the only pane, until the escape key
+    Terminal,
+    /// macro popup
+    Macros(MacroMenu),
+    /// macro editor popup, over the macro popup
+    MacroEdit(MacroEditor),
+    /// send file popup
+    SendFile(SendFileForm),
+    /// XMODEM/YMODEM/ZMODEM transfer popup
+    Transfer(TransferForm),
+    /// key bindings popup, scrolled down by the given number of lines
+    Help(u16),
+    /// port picker popup, to switch to another port
+    PickPort(PortPicker),
+    /// port picker popup, to open another port next to the others
+    AddPort(PortPicker),
 }
 
 impl Mode {
@@ -32,100 +67,1365 @@ impl Mode {
         matches!(self, Self::Normal)
     }
     pub fn is_config(&self) -> bool {
-        matches!(self, Self::Config)
+        matches!(self, Self::Config(_))
     }
     pub fn wanna_quit(&self) -> bool {
         matches!(self, Self::WannaQuit)
     }
+    pub fn is_search(&self) -> bool {
+        matches!(self, Self::Search(_))
+    }
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Terminal)
+    }
+}
+
+/// Console that scrolling and searching act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pane {
+    Tx,
+    Rx,
+}
+
+/// BREAK lengths offered by the config popup
+static BREAK_LENGTHS_MS: &[u64] = &[100, 250, 500, 1000, 2000, 5000];
+
+/// State of the config popup
+#[derive(Debug, Clone, Default)]
+pub struct ConfigMenu {
+    pub selected: usize,
+    /// error from the last setting applied, if the port rejected it
+    pub error: Option<String>,
+}
+
+impl ConfigMenu {
+    pub fn setting(&self) -> Setting {
+        Setting::ALL[self.selected]
+    }
+}
+
+/// State of the baud rate popup
+#[derive(Debug, Clone, Default)]
+pub struct BaudForm {
+    pub value: String,
+    pub error: Option<String>,
+}
+
+impl BaudForm {
+    /// The typed baud rate, if it is one
+    fn baud_rate(&self) -> Result<u32, String> {
+        match self.value.parse() {
+            Ok(0) => Err(String::from("the baud rate can't be 0")),
+            Ok(baud_rate) => Ok(baud_rate),
+            Err(_) if self.value.is_empty() => Err(String::from("type a baud rate")),
+            Err(_) => Err(format!("invalid baud rate `{}`", self.value)),
+        }
+    }
+}
+
+/// State of the macro popup
+#[derive(Debug, Clone, Default)]
+pub struct MacroMenu {
+    pub selected: usize,
+}
+
+/// State of the macro editor popup
+#[derive(Debug, Clone, Default)]
+pub struct MacroEditor {
+    /// macro being edited, `None` for a new one
+    pub index: Option<usize>,
+    pub name: String,
+    pub key: String,
+    pub data: String,
+    /// field being edited, see `FIELDS`
+    pub field: usize,
+    pub error: Option<String>,
+}
+
+impl MacroEditor {
+    pub const FIELDS: &'static [&'static str] = &["Name", "Key", "Data"];
+
+    fn edit(index: usize, m: &Macro) -> Self {
+        Self {
+            index: Some(index),
+            name: m.name.clone(),
+            key: m.key.map(|n| format!("F{n}")).unwrap_or_default(),
+            data: m.data.clone(),
+            ..Self::default()
+        }
+    }
+    pub fn value(&self, field: usize) -> &str {
+        match field {
+            0 => &self.name,
+            1 => &self.key,
+            _ => &self.data,
+        }
+    }
+    fn value_mut(&mut self) -> &mut String {
+        match self.field {
+            0 => &mut self.name,
+            1 => &mut self.key,
+            _ => &mut self.data,
+        }
+    }
+    /// The edited macro, if every field is valid
+    fn to_macro(&self) -> Result<Macro, String> {
+        let m = Macro {
+            name: self.name.trim().to_string(),
+            key: macros::parse_key(&self.key)?,
+            data: self.data.clone(),
+        };
+        if m.name.is_empty() {
+            return Err(String::from("the name can't be empty"));
+        }
+        // the macro file separates the name from the data with ` = `
+        if m.name.contains('=') {
+            return Err(String::from("the name can't contain `=`"));
+        }
+        m.bytes()?;
+        Ok(m)
+    }
+}
+
+/// State of the send file popup
+#[derive(Debug, Clone)]
+pub struct SendFileForm {
+    pub path: String,
+    pub char_delay: String,
+    pub line_delay: String,
+    /// field being edited, see `FIELDS`
+    pub field: usize,
+    /// candidates of an ambiguous path completion
+    pub completions: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl Default for SendFileForm {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            char_delay: String::from("0"),
+            line_delay: String::from("0"),
+            field: 0,
+            completions: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl SendFileForm {
+    pub const FIELDS: &'static [&'static str] = &["File", "Char delay ms", "Line delay ms"];
+
+    pub fn value(&self, field: usize) -> &str {
+        match field {
+            0 => &self.path,
+            1 => &self.char_delay,
+            _ => &self.line_delay,
+        }
+    }
+    fn value_mut(&mut self) -> &mut String {
+        match self.field {
+            0 => &mut self.path,
+            1 => &mut self.char_delay,
+            _ => &mut self.line_delay,
+        }
+    }
+    fn complete(&mut self) {
+        let (path, completions) = send_file::complete_path(&self.path);
+        self.path = path;
+        self.completions = completions;
+    }
+    /// Opens the file with the typed delays
+    fn start(&self) -> Result<FileSend, String> {
+        let delay = |name: &str, value: &str| {
+            value
+                .trim()
+                .parse()
+                .map(Duration::from_millis)
+                .map_err(|_| format!("invalid {name} `{value}`"))
+        };
+        let char_delay = delay("char delay", &self.char_delay)?;
+        let line_delay = delay("line delay", &self.line_delay)?;
+        let path = send_file::expand_home(self.path.trim());
+        FileSend::new(path, char_delay, line_delay).map_err(|e| e.to_string())
+    }
+}
+
+/// State of the file transfer popup
+#[derive(Debug, Clone)]
+pub struct TransferForm {
+    pub protocol: Protocol,
+    pub direction: transfer::Direction,
+    /// files to send, separated by spaces, or where to receive
+    pub path: String,
+    /// field being edited, see `FIELDS`
+    pub field: usize,
+    /// candidates of an ambiguous path completion
+    pub completions: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl Default for TransferForm {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::Ymodem,
+            direction: transfer::Direction::Send,
+            path: String::new(),
+            field: 0,
+            completions: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl TransferForm {
+    pub const FIELDS: &'static [&'static str] = &["Protocol", "Direction", "Path"];
+
+    /// Hint on what the path field holds
+    pub fn path_hint(&self) -> &'static str {
+        match (self.direction, self.protocol.is_batch()) {
+            (transfer::Direction::Send, true) => "files to send, separated by spaces",
+            (transfer::Direction::Send, false) => "file to send",
+            (transfer::Direction::Receive, true) => "directory to receive into (default .)",
+            (transfer::Direction::Receive, false) => "file to receive into",
+        }
+    }
+    fn cycle(&mut self, forward: bool) {
+        match self.field {
+            0 => self.protocol = serial::cycle(Protocol::ALL, self.protocol, forward),
+            1 => {
+                self.direction = match self.direction {
+                    transfer::Direction::Send => transfer::Direction::Receive,
+                    transfer::Direction::Receive => transfer::Direction::Send,
+                }
+            }
+            _ => (),
+        }
+    }
+    /// Completes the last path
+    fn complete(&mut self) {
+        let start = self.path.rfind(' ').map_or(0, |i| i + 1);
+        let (path, completions) = send_file::complete_path(&self.path[start..]);
+        self.path.truncate(start);
+        self.path.push_str(&path);
+        self.completions = completions;
+    }
+    fn to_request(&self) -> Result<Request, String> {
+        let mut paths = self
+            .path
+            .split_whitespace()
+            .map(send_file::expand_home)
+            .collect::<Vec<_>>();
+        match self.direction {
+            transfer::Direction::Send => {
+                if paths.is_empty() {
+                    return Err(String::from("no file to send"));
+                }
+                if paths.len() > 1 && !self.protocol.is_batch() {
+                    return Err(format!("{} sends a single file", self.protocol.name()));
+                }
+                if let Some(p) = paths.iter().find(|p| !p.is_file()) {
+                    return Err(format!("{} is not a file", p.display()));
+                }
+            }
+            transfer::Direction::Receive => {
+                if paths.len() > 1 {
+                    return Err(String::from("give a single path to receive into"));
+                }
+                if self.protocol.is_batch() {
+                    if paths.is_empty() {
+                        paths.push(".".into());
+                    }
+                    if !paths[0].is_dir() {
+                        return Err(format!("{} is not a directory", paths[0].display()));
+                    }
+                } else if paths.is_empty() {
+                    return Err(String::from("no file to receive into"));
+                }
+            }
+        }
+        Ok(Request {
+            protocol: self.protocol,
+            direction: self.direction,
+            paths,
+        })
+    }
+}
+
+/// Entries of the config popup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    BaudRate,
+    DataBits,
+    Parity,
+    StopBits,
+    FlowControl,
+    TxLineEnding,
+    RxNewline,
+    BreakDuration,
+    Encoding,
+    ControlStyle,
+    HexEscape,
+    DumpWidth,
+}
+
+impl Setting {
+    pub const ALL: &'static [Setting] = &[
+        Self::BaudRate,
+        Self::DataBits,
+        Self::Parity,
+        Self::StopBits,
+        Self::FlowControl,
+        Self::TxLineEnding,
+        Self::RxNewline,
+        Self::BreakDuration,
+        Self::Encoding,
+        Self::ControlStyle,
+        Self::HexEscape,
+        Self::DumpWidth,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BaudRate => "Baud rate",
+            Self::DataBits => "Data bits",
+            Self::Parity => "Parity",
+            Self::StopBits => "Stop bits",
+            Self::FlowControl => "Flow control",
+            Self::TxLineEnding => "TX line ending",
+            Self::RxNewline => "RX newline",
+            Self::BreakDuration => "BREAK length",
+            Self::Encoding => "Encoding",
+            Self::ControlStyle => "Control chars",
+            Self::HexEscape => "Mixed escapes",
+            Self::DumpWidth => "Hexdump width",
+        }
+    }
+}
+
+/// Transient notification shown in the status line
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub is_error: bool,
+    shown: Instant,
+}
+
+impl Message {
+    const TIMEOUT: Duration = Duration::from_secs(3);
+}
+
+/// How several open ports share the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortLayout {
+    /// a tab per port, only the current one shown
+    Tabs,
+    /// TX of the current port above the RX of every port
+    Tiles,
+}
+
+impl PortLayout {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tabs => "tabs",
+            Self::Tiles => "tiles",
+        }
+    }
 }
 
 pub struct App {
-    pub serial: Box<dyn SerialPort>,
-    pub tx: Tx,
-    pub rx: Rx,
+    /// open ports, never empty
+    pub sessions: Vec<Session>,
+    /// index of the port that keys, settings and the status line act on
+    pub current: usize,
+    pub layout: PortLayout,
+    /// RX of every port merged, fed while more than one port is open
+    pub timeline: Timeline,
+    /// the timeline is shown in place of RX
+    pub show_timeline: bool,
+    /// where the I/O threads of the ports opened later send their events
+    events: Sender<Event>,
+    /// open ports again once they come back after a disconnect
+    pub reconnect: bool,
+    pub break_duration: Duration,
+    pub send_mode: SendMode,
+    pub editor: LineEditor,
+    pub macros: Macros,
+    /// transfer to run on the current port, taking it over until it ends
+    pub transfer: Option<Request>,
+    /// key leaving terminal mode
+    pub escape_key: KeyEvent,
+    /// keys of the NORMAL mode actions
+    pub keymap: Keymap,
     pub mode: Mode,
+    pub focus: Pane,
+    pub message: Option<Message>,
     cursor: Cursor,
 }
 
 impl App {
-    pub fn new(serial: Box<dyn SerialPort>) -> Self {
+    /// how often the modem lines are polled
+    const MODEM_POLL: Duration = Duration::from_millis(100);
+    /// how often a disconnected port is looked for
+    const RECONNECT_POLL: Duration = Duration::from_secs(1);
+
+    /// App over `sessions`, the first one current. Ports opened later send their events
+    /// to `events`.
+    pub fn new(sessions: Vec<Session>, events: Sender<Event>) -> Self {
         Self {
-            serial,
-            tx: Tx::new(),
-            rx: Rx::new(),
+            sessions,
+            current: 0,
+            layout: PortLayout::Tabs,
+            timeline: Timeline::new(),
+            show_timeline: false,
+            events,
+            reconnect: false,
+            break_duration: Duration::from_millis(250),
+            send_mode: SendMode::Char,
+            editor: LineEditor::new(paths::state_dir().map(|d| d.join("history"))),
+            macros: Macros::default(),
+            transfer: None,
+            escape_key: KeyEvent::new(KeyCode::Char(']'), KeyModifiers::CONTROL),
+            keymap: Keymap::default(),
             mode: Mode::Normal,
+            focus: Pane::Rx,
+            message: None,
             cursor: Cursor::Normal,
         }
     }
-    pub fn update(&mut self, event: Option<Event>) -> Result<Control, io::Error> {
-        let mut ctl = Control::Continue;
-        let mut key_pressed = false;
-        if let Some(e) = event {
-            match e {
-                Event::Key(k) => {
-                    ctl = self.handle_key(k)?;
-                    key_pressed = true
+    /// Port that keys, settings and the status line act on
+    pub fn session(&self) -> &Session {
+        &self.sessions[self.current]
+    }
+    pub fn session_mut(&mut self) -> &mut Session {
+        &mut self.sessions[self.current]
+    }
+    /// Handles input from the terminal or data from a port
+    pub fn update(&mut self, event: Event) -> Result<Control, io::Error> {
+        match event {
+            Event::Input(input::Event::Key(k)) => {
+                let ctl = self.handle_key(k)?;
+                self.cursor.update(true);
+                return Ok(ctl);
+            }
+            Event::Input(_) => (),
+            // from the threads of a port since opened again
+            Event::Rx(tag, _) | Event::PortError(tag, _)
+                if self.sessions[tag.port].io.tag() != tag => {}
+            Event::Rx(tag, data) => self.receive(tag.port, &data)?,
+            // flow control holding the writes back for longer than the port timeout
+            Event::PortError(tag, e) if e.kind() == io::ErrorKind::TimedOut => {
+                match self.sessions[tag.port].sending.take() {
+                    Some(send) => {
+                        self.notify_error(format!("sending {} timed out", send.path.display()))
+                    }
+                    None => self.notify_error("write timed out"),
                 }
-                _ => (),
+            }
+            Event::PortError(tag, e) => self.disconnect(tag.port, e),
+        }
+        Ok(Control::Continue)
+    }
+    /// Runs what is due by now on every port: sending files, polling the modem lines,
+    /// ending BREAKs and reconnecting, then expires notifications. Returns whether the
+    /// screen changed.
+    pub fn tick(&mut self) -> Result<bool, io::Error> {
+        let mut changed = false;
+        for port in 0..self.sessions.len() {
+            changed |= self.send_file(port)?;
+            changed |= self.tick_port(port);
+        }
+        if self
+            .message
+            .as_ref()
+            .is_some_and(|m| m.shown.elapsed() >= Message::TIMEOUT)
+        {
+            self.message = None;
+            changed = true;
+        }
+        changed |= self.cursor.update(false);
+        Ok(changed)
+    }
+    /// Polls the modem lines of the `port`th port, or reconnects it, and ends its BREAK.
+    /// Returns whether anything changed.
+    fn tick_port(&mut self, port: usize) -> bool {
+        let mut changed = false;
+        let now = Instant::now();
+        let s = &mut self.sessions[port];
+        if s.disconnected {
+            if self.reconnect && now >= s.next_reconnect {
+                s.next_reconnect = now + Self::RECONNECT_POLL;
+                changed |= self.try_reconnect(port);
+            }
+        } else if now >= s.next_modem_poll {
+            s.next_modem_poll = now + Self::MODEM_POLL;
+            let modem = ModemStatus::poll(s.serial.as_mut());
+            changed |= modem != s.modem;
+            s.modem = modem;
+        }
+        let s = &mut self.sessions[port];
+        if s.break_until.is_some_and(|until| now >= until) {
+            s.break_until = None;
+            if let Err(e) = s.serial.clear_break() {
+                self.notify_error(format!("can't clear BREAK: {e}"));
+            }
+        }
+        changed
+    }
+    /// Time left until `tick` has something to do
+    pub fn next_tick(&self) -> Duration {
+        // nothing to poll at all while every port is disconnected
+        let mut next = Instant::now() + Self::RECONNECT_POLL;
+        for s in &self.sessions {
+            if !s.disconnected {
+                next = next.min(s.next_modem_poll);
+            } else if self.reconnect {
+                next = next.min(s.next_reconnect);
+            }
+            if let Some(send) = &s.sending {
+                next = next.min(send.next_at());
+            }
+            if let Some(until) = s.break_until {
+                next = next.min(until);
+            }
+        }
+        if let Some(m) = &self.message {
+            next = next.min(m.shown + Message::TIMEOUT);
+        }
+        if let Some(blink) = self.cursor.next_blink() {
+            next = next.min(blink);
+        }
+        next.saturating_duration_since(Instant::now())
+    }
+    fn receive(&mut self, port: usize, data: &[u8]) -> Result<(), io::Error> {
+        let merged = self.sessions.len() > 1;
+        let s = &mut self.sessions[port];
+        s.rx.receive(data);
+        if merged {
+            self.timeline.receive(port, s.name(), data, &s.rx.display);
+        }
+        if let Err(e) = s.logger.log(Direction::Rx, data) {
+            self.log_failed(port, e);
+        }
+        let s = &mut self.sessions[port];
+        let replies = s.rx.take_replies();
+        if !replies.is_empty() && !s.disconnected {
+            s.io.write_all(&replies)?;
+            if let Err(e) = s.logger.log(Direction::Tx, &replies) {
+                self.log_failed(port, e);
+            }
+        }
+        Ok(())
+    }
+    /// Action of NORMAL mode bound to `key`. Cancelling a file send only applies while one
+    /// runs, leaving its key to the next action, e.g. Esc quits otherwise.
+    fn normal_action(&self, key: KeyEvent) -> Option<Action> {
+        let sending = self.session().sending.is_some();
+        self.keymap
+            .actions(key)
+            .find(|&a| a != Action::CancelSend || sending)
+    }
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Help => self.mode = Mode::Help(0),
+            Action::Quit => self.mode = Mode::WannaQuit,
+            Action::InsertMode => self.enter_insert(),
+            Action::TerminalMode => self.enter_terminal(),
+            Action::Macros => self.mode = Mode::Macros(MacroMenu::default()),
+            Action::SendFile => self.mode = Mode::SendFile(SendFileForm::default()),
+            Action::CancelSend => self.cancel_send_file(self.current),
+            Action::Transfer => self.mode = Mode::Transfer(TransferForm::default()),
+            Action::SwitchPort => {
+                let mut picker = PortPicker::new();
+                picker.clear_history = Some(false);
+                self.mode = Mode::PickPort(picker);
+            }
+            Action::AddPort => self.mode = Mode::AddPort(PortPicker::new()),
+            Action::NextPort => self.current = (self.current + 1) % self.sessions.len(),
+            Action::PrevPort => {
+                let len = self.sessions.len();
+                self.current = (self.current + len - 1) % len;
+            }
+            Action::PortLayout => {
+                self.layout = match self.layout {
+                    PortLayout::Tabs => PortLayout::Tiles,
+                    PortLayout::Tiles => PortLayout::Tabs,
+                }
+            }
+            Action::Timeline if self.sessions.len() < 2 => {
+                self.notify_error("the timeline merges several ports, open another one first")
+            }
+            Action::Timeline => self.show_timeline = !self.show_timeline,
+            Action::RxView => self.session_mut().rx.display.switch_view(),
+            Action::RxAnsi => self.session_mut().rx.switch_ansi(),
+            Action::TxView => self.session_mut().tx.display.switch_view(),
+            Action::TxLineEnding => self.cycle_setting(Setting::TxLineEnding, true),
+            Action::RxNewline => self.cycle_setting(Setting::RxNewline, true),
+            Action::ToggleDtr => self.toggle_dtr(),
+            Action::ToggleRts => self.toggle_rts(),
+            Action::SendBreak => self.send_break(),
+            Action::ClearRx if self.show_timeline => self.timeline.clear(),
+            Action::ClearRx => self.session_mut().rx.clear(),
+            Action::ClearTx => self.session_mut().tx.display.clear(),
+            Action::BaudRate => self.mode = Mode::BaudInput(BaudForm::default()),
+            Action::Settings => self.mode = Mode::Config(ConfigMenu::default()),
+            Action::ToggleLog => self.toggle_log(),
+            Action::SendMode => {
+                self.send_mode = match self.send_mode {
+                    SendMode::Char => SendMode::Line,
+                    SendMode::Line => SendMode::Char,
+                }
+            }
+            Action::SwitchPane => {
+                self.focus = match self.focus {
+                    Pane::Tx => Pane::Rx,
+                    Pane::Rx => Pane::Tx,
+                }
+            }
+            Action::ScrollDown => self.focused().scroll.down(1),
+            Action::ScrollUp => self.focused().scroll.up(1),
+            Action::HalfPageDown => {
+                let display = self.focused();
+                display.scroll.down(display.scroll.half_page());
+            }
+            Action::HalfPageUp => {
+                let display = self.focused();
+                display.scroll.up(display.scroll.half_page());
+            }
+            Action::PageDown => {
+                let display = self.focused();
+                display.scroll.down(display.scroll.height);
+            }
+            Action::PageUp => {
+                let display = self.focused();
+                display.scroll.up(display.scroll.height);
+            }
+            Action::Top => self.focused().scroll.to_top(),
+            Action::Bottom => self.focused().scroll.to_bottom(),
+            Action::Search => {
+                self.focused().search = Some(Search::default());
+                self.mode = Mode::Search(String::new());
+            }
+            Action::NextMatch => {
+                if let Some(search) = &mut self.focused().search {
+                    search.next();
+                }
+            }
+            Action::PrevMatch => {
+                if let Some(search) = &mut self.focused().search {
+                    search.prev();
+                }
+            }
+            Action::Timestamps if self.show_timeline => {
+                let display = &mut self.timeline.display;
+                display.timestamps = display.timestamps.next();
+            }
+            Action::Timestamps => {
+                let s = self.session_mut();
+                s.rx.display.timestamps = s.rx.display.timestamps.next();
+                s.logger.timestamps = s.rx.display.timestamps;
             }
         }
-        self.rx.recv(self.serial.as_mut())?;
-        self.cursor.update(key_pressed);
-        Ok(ctl)
     }
     fn handle_key(&mut self, key: KeyEvent) -> Result<Control, io::Error> {
         use KeyCode as K;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
         match &mut self.mode {
+            Mode::Insert if self.send_mode == SendMode::Line => {
+                if key.code == K::Esc {
+                    self.leave_insert();
+                } else if let Some(line) = self.editor.handle_key(key) {
+                    self.send_line(&line)?;
+                }
+            }
+            Mode::Terminal if keys::is_key(key, self.escape_key) => self.leave_insert(),
+            Mode::Terminal => {
+                let app_cursor = self.session().rx.vt.as_ref().is_some_and(Vt::app_cursor);
+                let bytes = keys::key_bytes(key, app_cursor);
+                self.send_raw(&bytes)?;
+            }
             Mode::Insert => match key.code {
                 K::Esc => self.leave_insert(),
+                K::Char(c) if ctrl => {
+                    if let Some(b) = keys::control_byte(c) {
+                        self.send_raw(&[b])?;
+                    }
+                }
                 K::Char(c) => {
                     let mut buf = [0; 4];
                     for &b in c.encode_utf8(&mut buf).as_bytes() {
-                        self.tx.send(b, self.serial.as_mut())?;
+                        self.send(b)?;
                     }
                 }
-                K::Tab => self.tx.send(b'\t', self.serial.as_mut())?,
-                K::Enter => self.tx.send(b'\n', self.serial.as_mut())?,
+                K::Tab => self.send(b'\t')?,
+                K::Enter => self.send(b'\n')?,
                 _ => (),
             },
-            Mode::Normal => match key.code {
-                K::Esc | KeyCode::Char('q') => self.mode = Mode::WannaQuit,
-                K::Char('i') => self.enter_insert(),
-                K::Char('h') => self.rx.display.switch_hex(),
-                K::Char('H') => self.tx.display.switch_hex(),
-                K::Char('l') => self.tx.lf_crlf = !self.tx.lf_crlf,
-                K::Char('c') => self.rx.display.clear(),
-                K::Char('C') => self.tx.display.clear(),
-                K::Char('b') => self.mode = Mode::BaudInput(String::with_capacity(8)),
-
-                _ => (),
+            Mode::Normal => match self.normal_action(key) {
+                Some(action) => self.run_action(action),
+                None => {
+                    if let K::F(n) = key.code {
+                        self.send_macro_key(n)?;
+                    }
+                }
+            },
+            Mode::Help(scroll) => match key.code {
+                K::Down | K::Char('j') => *scroll = (*scroll + 1).min(Action::ALL.len() as u16),
+                K::Up | K::Char('k') => *scroll = scroll.saturating_sub(1),
+                _ => self.mode = Mode::Normal,
+            },
+            Mode::PickPort(picker) => match picker.handle_key(key) {
+                Pick::None => (),
+                Pick::Cancel => self.mode = Mode::Normal,
+                Pick::Selected(path) => {
+                    let clear = picker.clear_history == Some(true);
+                    self.mode = Mode::Normal;
+                    self.switch_port(self.current, &path, clear);
+                }
+            },
+            Mode::AddPort(picker) => match picker.handle_key(key) {
+                Pick::None => (),
+                Pick::Cancel => self.mode = Mode::Normal,
+                Pick::Selected(path) => {
+                    self.mode = Mode::Normal;
+                    self.add_port(&path);
+                }
             },
             Mode::WannaQuit => match key.code {
                 K::Esc | K::Char('n' | 'q') => self.mode = Mode::Normal,
                 K::Char('y') => return Ok(Control::Exit),
                 _ => (),
             },
-            Mode::BaudInput(buf) => match key.code {
+            Mode::BaudInput(form) => match key.code {
                 K::Esc => self.mode = Mode::Normal,
-                K::Char(c @ '0'..='9') => buf.push(c),
+                K::Char(c @ '0'..='9') => form.value.push(c),
+                K::Enter if self.sessions[self.current].disconnected => {
+                    form.error = Some(String::from("port disconnected"));
+                }
+                K::Enter => match form.baud_rate() {
+                    Ok(baud_rate) => {
+                        let s = &mut self.sessions[self.current];
+                        let line = LineConfig {
+                            baud_rate,
+                            ..s.line
+                        };
+                        match line.apply(s.serial.as_mut()) {
+                            Ok(()) => {
+                                s.line = line;
+                                self.mode = Mode::Normal;
+                            }
+                            Err(e) => {
+                                // put the port back in a known state
+                                let _ = s.line.apply(s.serial.as_mut());
+                                self.notify_error(format!("can't set {baud_rate} baud: {e}"));
+                            }
+                        }
+                    }
+                    Err(e) => form.error = Some(e),
+                },
+                K::Backspace => {
+                    form.value.pop();
+                }
+                _ => (),
+            },
+            Mode::Search(query) => match key.code {
+                K::Esc => {
+                    self.focused().search = None;
+                    self.mode = Mode::Normal;
+                }
                 K::Enter => {
-                    self.serial.set_baud_rate(buf.parse().unwrap())?;
+                    if query.is_empty() {
+                        self.focused().search = None;
+                    }
                     self.mode = Mode::Normal;
                 }
                 K::Backspace => {
-                    buf.pop();
+                    query.pop();
+                    let query = query.clone();
+                    self.update_search(query);
+                }
+                K::Char(c) => {
+                    query.push(c);
+                    let query = query.clone();
+                    self.update_search(query);
+                }
+                _ => (),
+            },
+            Mode::Macros(menu) => match key.code {
+                K::Esc | K::Char('q' | 'm') => self.mode = Mode::Normal,
+                K::Down | K::Char('j') if !self.macros.list.is_empty() => {
+                    menu.selected = (menu.selected + 1) % self.macros.list.len();
+                }
+                K::Up | K::Char('k') if !self.macros.list.is_empty() => {
+                    let len = self.macros.list.len();
+                    menu.selected = (menu.selected + len - 1) % len;
+                }
+                K::Enter if menu.selected < self.macros.list.len() => {
+                    let selected = menu.selected;
+                    self.mode = Mode::Normal;
+                    self.send_macro(selected)?;
+                }
+                K::F(n) => {
+                    self.mode = Mode::Normal;
+                    self.send_macro_key(n)?;
+                }
+                K::Char('n') => self.mode = Mode::MacroEdit(MacroEditor::default()),
+                K::Char('e') => {
+                    if let Some(m) = self.macros.list.get(menu.selected) {
+                        self.mode = Mode::MacroEdit(MacroEditor::edit(menu.selected, m));
+                    }
+                }
+                K::Char('d') if menu.selected < self.macros.list.len() => {
+                    self.macros.list.remove(menu.selected);
+                    menu.selected = menu.selected.min(self.macros.list.len().saturating_sub(1));
+                    self.save_macros();
+                }
+                _ => (),
+            },
+            Mode::MacroEdit(editor) => match key.code {
+                K::Esc => {
+                    self.mode = Mode::Macros(MacroMenu {
+                        selected: editor.index.unwrap_or(0),
+                    })
+                }
+                K::Tab | K::Down => editor.field = (editor.field + 1) % MacroEditor::FIELDS.len(),
+                K::BackTab | K::Up => {
+                    let len = MacroEditor::FIELDS.len();
+                    editor.field = (editor.field + len - 1) % len;
+                }
+                K::Backspace => {
+                    editor.value_mut().pop();
+                }
+                K::Char(c) if !ctrl => editor.value_mut().push(c),
+                // pressing a function key in the key field binds it
+                K::F(n) if editor.field == 1 => editor.key = format!("F{n}"),
+                K::Enter => match editor.to_macro() {
+                    Ok(m) => {
+                        let selected = self.macros.put(editor.index, m);
+                        self.mode = Mode::Macros(MacroMenu { selected });
+                        self.save_macros();
+                    }
+                    Err(e) => editor.error = Some(e),
+                },
+                _ => (),
+            },
+            Mode::SendFile(form) => match key.code {
+                K::Esc => self.mode = Mode::Normal,
+                K::Tab if form.field == 0 => form.complete(),
+                K::Tab | K::Down => {
+                    form.field = (form.field + 1) % SendFileForm::FIELDS.len();
+                }
+                K::BackTab | K::Up => {
+                    let len = SendFileForm::FIELDS.len();
+                    form.field = (form.field + len - 1) % len;
+                }
+                K::Backspace => {
+                    form.value_mut().pop();
+                }
+                K::Char(c) if !ctrl => form.value_mut().push(c),
+                K::Enter if self.sessions[self.current].sending.is_some() => {
+                    form.error = Some(String::from("a file is already being sent"));
+                }
+                K::Enter if self.sessions[self.current].disconnected => {
+                    form.error = Some(String::from("port disconnected"));
+                }
+                K::Enter => match form.start() {
+                    Ok(send) => {
+                        self.sessions[self.current].sending = Some(send);
+                        self.mode = Mode::Normal;
+                    }
+                    Err(e) => form.error = Some(e),
+                },
+                _ => (),
+            },
+            Mode::Transfer(form) => match key.code {
+                K::Esc => self.mode = Mode::Normal,
+                K::Tab if form.field == 2 => form.complete(),
+                K::Tab | K::Down => form.field = (form.field + 1) % TransferForm::FIELDS.len(),
+                K::BackTab | K::Up => {
+                    let len = TransferForm::FIELDS.len();
+                    form.field = (form.field + len - 1) % len;
+                }
+                K::Left | K::Char('h') if form.field < 2 => form.cycle(false),
+                K::Right | K::Char('l' | ' ') if form.field < 2 => form.cycle(true),
+                K::Backspace => {
+                    form.path.pop();
+                }
+                K::Char(c) if !ctrl && form.field == 2 => form.path.push(c),
+                K::Enter if self.sessions[self.current].sending.is_some() => {
+                    form.error = Some(String::from("a file is being sent"));
+                }
+                K::Enter if self.sessions[self.current].disconnected => {
+                    form.error = Some(String::from("port disconnected"));
+                }
+                K::Enter => match form.to_request() {
+                    Ok(request) => {
+                        self.transfer = Some(request);
+                        self.mode = Mode::Normal;
+                    }
+                    Err(e) => form.error = Some(e),
+                },
+                _ => (),
+            },
+            Mode::Config(menu) => match key.code {
+                K::Esc | K::Char('q' | 's') => self.mode = Mode::Normal,
+                K::Down | K::Char('j') => {
+                    menu.selected = (menu.selected + 1) % Setting::ALL.len();
+                }
+                K::Up | K::Char('k') => {
+                    menu.selected = (menu.selected + Setting::ALL.len() - 1) % Setting::ALL.len();
+                }
+                K::Right | K::Char('l') | K::Enter | K::Char(' ') => {
+                    let setting = menu.setting();
+                    self.cycle_setting(setting, true);
+                }
+                K::Left | K::Char('h') => {
+                    let setting = menu.setting();
+                    self.cycle_setting(setting, false);
                 }
                 _ => (),
             },
-            _ => (),
         }
         Ok(Control::Continue)
     }
 
+    /// Display of the focused pane, the timeline standing for RX while it is shown
+    pub fn focused(&mut self) -> &mut Display {
+        let s = &mut self.sessions[self.current];
+        match self.focus {
+            Pane::Tx => &mut s.tx.display,
+            Pane::Rx if self.show_timeline => &mut self.timeline.display,
+            Pane::Rx => &mut s.rx.display,
+        }
+    }
+    /// Restarts the incremental search with an edited query
+    fn update_search(&mut self, query: String) {
+        self.focused().search = Some(Search {
+            query,
+            ..Search::default()
+        });
+    }
+    /// Sends a typed character through TX, logging what went out
+    fn send(&mut self, ch: u8) -> Result<(), io::Error> {
+        let s = &mut self.sessions[self.current];
+        if s.disconnected {
+            self.notify_error("not sent: port disconnected");
+            return Ok(());
+        }
+        let sent = s.tx.send(ch, &mut s.io)?;
+        if let Err(e) = s.logger.log(Direction::Tx, sent) {
+            self.log_failed(self.current, e);
+        }
+        Ok(())
+    }
+    /// Sends a line from the line editor followed by the line ending. Escapes are expanded,
+    /// unless TX is in a hex view where the line is hex digits.
+    fn send_line(&mut self, line: &str) -> Result<(), io::Error> {
+        if self.session().tx.is_hex() {
+            for b in line.bytes() {
+                self.send(b)?;
+            }
+            // Enter is not a hex digit, the line ending goes out as is
+            let eol = self.session().tx.line_ending.bytes();
+            return self.send_raw(eol);
+        }
+        match escape::unescape(line) {
+            Ok(bytes) => self.send_raw(&bytes)?,
+            Err(e) => {
+                self.notify_error(format!("not sent: {e}"));
+                return Ok(());
+            }
+        }
+        self.send(b'\n')
+    }
+    fn send_macro_key(&mut self, key: u8) -> Result<(), io::Error> {
+        match self.macros.list.iter().position(|m| m.key == Some(key)) {
+            Some(i) => self.send_macro(i),
+            None => {
+                self.notify_error(format!("no macro on F{key}"));
+                Ok(())
+            }
+        }
+    }
+    fn send_macro(&mut self, index: usize) -> Result<(), io::Error> {
+        let m = &self.macros.list[index];
+        match m.bytes() {
+            Ok(bytes) => {
+                let name = m.name.clone();
+                self.send_raw(&bytes)?;
+                self.notify(format!("sent {name}"));
+            }
+            Err(e) => self.notify_error(format!("macro {}: {e}", m.name)),
+        }
+        Ok(())
+    }
+    fn save_macros(&mut self) {
+        if let Err(e) = self.macros.save() {
+            self.notify_error(format!("can't save macros: {e}"));
+        }
+    }
+    /// Sends bytes as they are, bypassing TX line ending and hex input
+    fn send_raw(&mut self, bytes: &[u8]) -> Result<(), io::Error> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        let s = &mut self.sessions[self.current];
+        if s.disconnected {
+            self.notify_error("not sent: port disconnected");
+            return Ok(());
+        }
+        s.io.write_all(bytes)?;
+        s.tx.display.push_bytes(bytes);
+        if let Err(e) = s.logger.log(Direction::Tx, bytes) {
+            self.log_failed(self.current, e);
+        }
+        Ok(())
+    }
+    /// Sends the part of the file being sent on the `port`th port that is due, without
+    /// waiting for the rest. Returns whether anything was sent.
+    fn send_file(&mut self, port: usize) -> Result<bool, io::Error> {
+        let s = &mut self.sessions[port];
+        let Some(send) = &mut s.sending else {
+            return Ok(false);
+        };
+        let sent = send.poll(&mut s.io, s.line.baud_rate)?;
+        if sent.is_empty() {
+            return Ok(false);
+        }
+        s.tx.display.push_bytes(sent);
+        if let Err(e) = s.logger.log(Direction::Tx, sent) {
+            self.log_failed(port, e);
+        }
+        if let Some(send) = self.sessions[port].sending.take_if(|s| s.is_done()) {
+            self.notify(format!(
+                "sent {} ({} bytes)",
+                send.path.display(),
+                send.len()
+            ));
+        }
+        Ok(true)
+    }
+    fn cancel_send_file(&mut self, port: usize) {
+        let s = &mut self.sessions[port];
+        if let Some(send) = s.sending.take() {
+            let event = format!(
+                "sending {} cancelled after {} bytes",
+                send.path.display(),
+                send.sent()
+            );
+            s.tx.display.push_event(&event);
+            if let Err(e) = s.logger.event(&event) {
+                self.log_failed(port, e);
+            }
+        }
+    }
+    /// Records the outcome of a transfer on the current port in its consoles and log
+    pub fn transfer_done(&mut self, request: &Request, res: transfer::Result<transfer::Summary>) {
+        let name = format!("{} {}", request.protocol.name(), request.direction.name());
+        let event = match &res {
+            Ok(summary) => format!("{name}: {summary}"),
+            Err(e) => format!("{name} failed: {e}"),
+        };
+        self.port_event(self.current, event.clone());
+        match res {
+            Ok(_) => self.notify(event),
+            Err(_) => self.notify_error(event),
+        }
+    }
+    /// Keeps the `port`th port's session going without it after it failed, until it is
+    /// reconnected
+    fn disconnect(&mut self, port: usize, e: io::Error) {
+        let s = &mut self.sessions[port];
+        if s.disconnected {
+            // what was still queued for the dead port
+            return;
+        }
+        s.disconnected = true;
+        s.next_reconnect = Instant::now() + Self::RECONNECT_POLL;
+        s.break_until = None;
+        s.modem = ModemStatus::default();
+        self.cancel_send_file(port);
+        self.port_event(port, format!("disconnected: {e}"));
+        let path = &self.sessions[port].port_id.path;
+        self.notify_error(format!("{path} disconnected: {e}"));
+    }
+    /// Opens the `port`th port again if it is back, with its line settings and control
+    /// lines. Returns whether it was.
+    fn try_reconnect(&mut self, port: usize) -> bool {
+        let s = &mut self.sessions[port];
+        let Some(path) = s.port_id.find() else {
+            return false;
+        };
+        // still settling, e.g. udev fixing permissions, tried again on the next poll
+        let Ok(serial) = s.open_port(&path) else {
+            return false;
+        };
+        s.serial = serial;
+        s.disconnected = false;
+        s.next_modem_poll = Instant::now();
+        self.port_event(port, format!("reconnected to {path}"));
+        self.notify(format!("reconnected to {path}"));
+        true
+    }
+    /// Swaps the `port`th port for the one at `path`, opened with the same line settings
+    /// and control lines. The consoles are cleared with `clear`, else the switch is
+    /// recorded in them.
+    fn switch_port(&mut self, port: usize, path: &str, clear: bool) {
+        let other = |(i, s): (usize, &Session)| i != port && s.port_id.path == path;
+        if self.sessions.iter().enumerate().any(other) {
+            self.notify_error(format!("{path} is already open"));
+            return;
+        }
+        let s = &mut self.sessions[port];
+        let serial = match s.open_port(path) {
+            Ok(serial) => serial,
+            Err(e) => {
+                // back to the port it was on
+                if !s.disconnected {
+                    if let Err(e) = s.io.restart(s.serial.as_ref()) {
+                        self.disconnect(port, e.into());
+                    }
+                }
+                self.notify_error(format!("can't open {path}: {e}"));
+                return;
+            }
+        };
+        self.cancel_send_file(port);
+        let s = &mut self.sessions[port];
+        if s.break_until.take().is_some() {
+            let _ = s.serial.clear_break();
+        }
+        s.serial = serial;
+        s.port_id = PortId::of(path);
+        s.disconnected = false;
+        s.modem = ModemStatus::default();
+        s.next_modem_poll = Instant::now();
+        if clear {
+            s.tx.display.clear();
+            s.rx.clear();
+        } else {
+            self.port_event(port, format!("switched to {path}"));
+        }
+        self.notify(format!("switched to {path}"));
+    }
+    /// Opens the port at `path` next to the others and makes it current. It starts with
+    /// the settings of the current port, and logs if that one does.
+    fn add_port(&mut self, path: &str) {
+        if let Some(i) = self.sessions.iter().position(|s| s.port_id.path == path) {
+            self.current = i;
+            self.notify(format!("{path} is already open"));
+            return;
+        }
+        let index = self.sessions.len();
+        let from = self.session();
+        let open = || {
+            let mut serial = serial::open(path, &from.line)?;
+            // not every port has control lines, e.g. a pty
+            let _ = serial.write_data_terminal_ready(from.dtr);
+            let _ = serial.write_request_to_send(from.rts);
+            let io = PortIo::spawn(serial.as_ref(), index, self.events.clone())?;
+            serialport::Result::Ok((serial, io))
+        };
+        let (serial, io) = match open() {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.notify_error(format!("can't open {path}: {e}"));
+                return;
+            }
+        };
+        let logger = from.logger.for_port(session::port_name(path));
+        let mut s = Session::new(serial, PortId::of(path), io, from.line, logger);
+        s.dtr = from.dtr;
+        s.rts = from.rts;
+        s.tx.line_ending = from.tx.line_ending;
+        for (display, like) in [
+            (&mut s.tx.display, &from.tx.display),
+            (&mut s.rx.display, &from.rx.display),
+        ] {
+            display.set_newline(like.newline());
+            display.set_encoding(like.encoding());
+            display.set_control_style(like.control_style());
+            display.set_hex_escape(like.hex_escape);
+            display.dump_width = like.dump_width;
+            display.scrollback = like.scrollback;
+            display.timestamps = like.timestamps;
+        }
+        if from.rx.is_ansi() {
+            s.rx.switch_ansi();
+        }
+        s.logger.timestamps = from.logger.timestamps;
+        let log = from.logger.is_active();
+        self.sessions.push(s);
+        self.current = index;
+        if log {
+            if let Err(e) = self.session_mut().logger.start() {
+                self.log_failed(index, e);
+                return;
+            }
+        }
+        self.notify(format!("opened {path}"));
+    }
+    /// Records a change of the `port`th port in its consoles, the timeline and its log
+    fn port_event(&mut self, port: usize, event: String) {
+        let merged = self.sessions.len() > 1;
+        let s = &mut self.sessions[port];
+        if merged {
+            self.timeline.push_event(port, s.name(), &event);
+        }
+        if let Err(e) = s.push_event(&event) {
+            self.log_failed(port, e);
+        }
+    }
+    fn toggle_dtr(&mut self) {
+        let s = self.session_mut();
+        match s.serial.write_data_terminal_ready(!s.dtr) {
+            Ok(()) => s.dtr = !s.dtr,
+            Err(e) => self.notify_error(format!("can't set DTR: {e}")),
+        }
+    }
+    fn toggle_rts(&mut self) {
+        let s = self.session_mut();
+        match s.serial.write_request_to_send(!s.rts) {
+            Ok(()) => s.rts = !s.rts,
+            Err(e) => self.notify_error(format!("can't set RTS: {e}")),
+        }
+    }
+    /// Starts a BREAK condition, cleared by `tick` after `break_duration`
+    fn send_break(&mut self) {
+        let duration = self.break_duration;
+        let s = self.session_mut();
+        if s.break_until.is_some() {
+            return;
+        }
+        if let Err(e) = s.serial.set_break() {
+            self.notify_error(format!("can't send BREAK: {e}"));
+            return;
+        }
+        s.break_until = Some(Instant::now() + duration);
+        self.port_event(self.current, format!("BREAK {} ms", duration.as_millis()));
+    }
+    fn toggle_log(&mut self) {
+        let logger = &mut self.session_mut().logger;
+        match logger.toggle() {
+            Ok(()) => match logger.path().map(|p| p.display().to_string()) {
+                Some(p) => self.notify(format!("logging to {p}")),
+                None => self.notify("logging stopped"),
+            },
+            Err(e) => self.log_failed(self.current, e),
+        }
+    }
+    /// Stops logging the `port`th port after a write error instead of tearing down the
+    /// session
+    fn log_failed(&mut self, port: usize, e: io::Error) {
+        let _ = self.sessions[port].logger.stop();
+        self.notify_error(format!("log error: {e}"));
+    }
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            is_error: false,
+            shown: Instant::now(),
+        });
+    }
+    pub fn notify_error(&mut self, text: impl Into<String>) {
+        self.message = Some(Message {
+            text: text.into(),
+            is_error: true,
+            shown: Instant::now(),
+        });
+    }
+
+    /// Current value of `setting` on the current port, as shown in the config popup
+    pub fn setting_value(&self, setting: Setting) -> String {
+        let s = self.session();
+        match setting {
+            Setting::BaudRate => s.line.baud_rate.to_string(),
+            Setting::DataBits => serial::data_bits_str(s.line.data_bits).to_string(),
+            Setting::Parity => serial::parity_str(s.line.parity).to_string(),
+            Setting::StopBits => serial::stop_bits_str(s.line.stop_bits).to_string(),
+            Setting::FlowControl => serial::flow_control_str(s.line.flow_control).to_string(),
+            Setting::TxLineEnding => s.tx.line_ending.name().to_string(),
+            Setting::RxNewline => s.rx.display.newline().name().to_string(),
+            Setting::BreakDuration => format!("{} ms", self.break_duration.as_millis()),
+            Setting::Encoding => s.rx.display.encoding().name().to_string(),
+            Setting::ControlStyle => s.rx.display.control_style().name().to_string(),
+            Setting::HexEscape => s.rx.display.hex_escape.name().to_string(),
+            Setting::DumpWidth => s.rx.display.dump_width.name(),
+        }
+    }
+    /// Moves `setting` to its next (or previous) value, applying it to the current port
+    fn cycle_setting(&mut self, setting: Setting, forward: bool) {
+        let s = &mut self.sessions[self.current];
+        let mut line = s.line;
+        match setting {
+            Setting::TxLineEnding => {
+                s.tx.line_ending = serial::cycle(LineEnding::ALL, s.tx.line_ending, forward);
+                return;
+            }
+            Setting::RxNewline => {
+                let newline = serial::cycle(RxNewline::ALL, s.rx.display.newline(), forward);
+                s.rx.display.set_newline(newline);
+                return;
+            }
+            Setting::Encoding => {
+                let encoding = serial::cycle(Encoding::ALL, s.rx.display.encoding(), forward);
+                s.rx.set_encoding(encoding);
+                s.tx.display.set_encoding(encoding);
+                return;
+            }
+            Setting::ControlStyle => {
+                let style = serial::cycle(ControlStyle::ALL, s.rx.display.control_style(), forward);
+                s.rx.display.set_control_style(style);
+                s.tx.display.set_control_style(style);
+                return;
+            }
+            Setting::HexEscape => {
+                let escape = serial::cycle(HexEscape::ALL, s.rx.display.hex_escape, forward);
+                s.rx.display.set_hex_escape(escape);
+                s.tx.display.set_hex_escape(escape);
+                return;
+            }
+            Setting::DumpWidth => {
+                let width = serial::cycle(DumpWidth::ALL, s.rx.display.dump_width, forward);
+                s.rx.display.dump_width = width;
+                s.tx.display.dump_width = width;
+                return;
+            }
+            Setting::BreakDuration => {
+                let ms = self.break_duration.as_millis() as u64;
+                let ms = serial::cycle(BREAK_LENGTHS_MS, ms, forward);
+                self.break_duration = Duration::from_millis(ms);
+                return;
+            }
+            Setting::BaudRate => line.baud_rate = serial::cycle_baud_rate(line.baud_rate, forward),
+            Setting::DataBits => line.data_bits = serial::cycle_data_bits(line.data_bits, forward),
+            Setting::Parity => line.parity = serial::cycle_parity(line.parity, forward),
+            Setting::StopBits => line.stop_bits = serial::cycle_stop_bits(line.stop_bits, forward),
+            Setting::FlowControl => {
+                line.flow_control = serial::cycle_flow_control(line.flow_control, forward)
+            }
+        }
+        let res = line.apply(s.serial.as_mut());
+        if let Mode::Config(menu) = &mut self.mode {
+            menu.error = res.as_ref().err().map(|e| e.to_string());
+        }
+        match res {
+            Ok(()) => s.line = line,
+            // put the port back in a known state
+            Err(_) => {
+                let _ = s.line.apply(s.serial.as_mut());
+            }
+        }
+    }
+
     pub fn enter_insert(&mut self) {
         self.mode = Mode::Insert;
         self.cursor = Cursor::insert();
     }
+    pub fn enter_terminal(&mut self) {
+        self.mode = Mode::Terminal;
+        self.cursor = Cursor::insert();
+        self.session_mut().rx.display.scroll.to_bottom();
+    }
     pub fn leave_insert(&mut self) {
         self.mode = Mode::Normal;
         self.cursor = Cursor::normal();
@@ -139,8 +1439,8 @@ enum Cursor {
     Normal,
     Insert {
         on: bool,
-        timer: Duration,
-        last: Instant,
+        /// last blink or key press
+        since: Instant,
     },
 }
 
@@ -155,25 +1455,32 @@ impl Cursor {
     fn insert() -> Self {
         Self::Insert {
             on: true,
-            timer: Duration::ZERO,
-            last: Instant::now(),
+            since: Instant::now(),
         }
     }
 
-    fn update(&mut self, key_pressed: bool) {
-        if let Self::Insert { on, timer, last } = self {
-            if key_pressed {
-                *on = true;
-                *timer = Duration::ZERO;
-                return;
-            }
-            let now = Instant::now();
-            *timer += now - *last;
-            *last = now;
-            if *timer > Self::BLINK_SPEED {
-                *on = !*on;
-                *timer -= Self::BLINK_SPEED;
-            }
+    /// Blinks the insert cursor, keeping it on while typing. Returns whether it changed.
+    fn update(&mut self, key_pressed: bool) -> bool {
+        let Self::Insert { on, since } = self else {
+            return false;
+        };
+        if key_pressed {
+            let changed = !*on;
+            *on = true;
+            *since = Instant::now();
+            return changed;
+        }
+        if since.elapsed() < Self::BLINK_SPEED {
+            return false;
+        }
+        *on = !*on;
+        *since = Instant::now();
+        true
+    }
+    fn next_blink(&self) -> Option<Instant> {
+        match self {
+            Self::Normal => None,
+            Self::Insert { since, .. } => Some(*since + Self::BLINK_SPEED),
         }
     }
     fn cursor(&self) -> char {
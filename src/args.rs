@@ -1,13 +1,270 @@
+use crate::clock::{parse_timestamp_mode, TimestampMode};
+use crate::config::Profile;
+use crate::decode::{
+    parse_control_style, parse_encoding, parse_hex_escape, ControlStyle, Encoding, HexEscape,
+};
+use crate::keys::parse_key;
+use crate::line_ending::{parse_line_ending, parse_rx_newline, LineEnding, RxNewline};
+use crate::logger::{parse_log_format, parse_size, LogFormat};
+use crate::screen::{parse_dump_width, parse_view, DumpWidth, View};
+use crate::serial::{
+    parse_data_bits, parse_flow_control, parse_level, parse_parity, parse_stop_bits, LineConfig,
+};
 use argh::FromArgs;
+use crossterm::event::KeyEvent;
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::path::PathBuf;
 
 #[derive(FromArgs)]
 /// A tui serial terminal
 pub struct Args {
     #[argh(option, short = 'b')]
-    /// baud rate
-    pub baud: u32,
+    /// baud rate, required unless set in the config file
+    pub baud: Option<u32>,
 
-    /// serial port to connect to
+    #[argh(option, short = 'd', from_str_fn(parse_data_bits))]
+    /// data bits: 5, 6, 7 or 8 (default 8)
+    pub data_bits: Option<DataBits>,
+
+    #[argh(option, short = 'p', from_str_fn(parse_parity))]
+    /// parity: none, odd or even (default none)
+    pub parity: Option<Parity>,
+
+    #[argh(option, short = 's', from_str_fn(parse_stop_bits))]
+    /// stop bits: 1 or 2 (default 1)
+    pub stop_bits: Option<StopBits>,
+
+    #[argh(option, short = 'f', from_str_fn(parse_flow_control))]
+    /// flow control: none, software or hardware (default none)
+    pub flow_control: Option<FlowControl>,
+
+    #[argh(option, from_str_fn(parse_level))]
+    /// initial DTR level: on or off (left as the driver sets it if omitted).
+    /// The line is lowered right after the port opens, so boards that reset on DTR may
+    /// still see a pulse
+    pub dtr: Option<bool>,
+
+    #[argh(option, from_str_fn(parse_level))]
+    /// initial RTS level: on or off (left as the driver sets it if omitted)
+    pub rts: Option<bool>,
+
+    #[argh(option)]
+    /// length of the BREAK condition sent with `B`, in milliseconds (default 250)
+    pub break_ms: Option<u64>,
+
+    #[argh(option, from_str_fn(parse_line_ending))]
+    /// line ending sent on Enter: none, cr, lf, crlf or lfcr (default lf)
+    pub tx_eol: Option<LineEnding>,
+
+    #[argh(option, from_str_fn(parse_rx_newline))]
+    /// received bytes that break lines: lf, cr or any of CR, LF and CRLF (default lf)
+    pub rx_eol: Option<RxNewline>,
+
+    #[argh(option, from_str_fn(parse_encoding))]
+    /// text encoding of the ASCII view: utf8, latin1 or ascii (default utf8)
+    pub encoding: Option<Encoding>,
+
+    #[argh(option, from_str_fn(parse_control_style))]
+    /// how control characters are shown: picture (␛), hex (<0x1B>) or caret (^[)
+    /// (default picture)
+    pub control: Option<ControlStyle>,
+
+    #[argh(option, from_str_fn(parse_hex_escape))]
+    /// how the mixed view escapes non-printable bytes: angle (<1B>) or backslash (\x1B)
+    /// (default angle)
+    pub hex_escape: Option<HexEscape>,
+
+    #[argh(option, from_str_fn(parse_dump_width))]
+    /// bytes per row of the hexdump view: auto, 8, 16 or 32 (default auto)
+    pub dump_width: Option<DumpWidth>,
+
+    #[argh(option, from_str_fn(parse_view))]
+    /// starting view of the RX console: text, mixed, hex or hexdump (default text)
+    pub rx_view: Option<View>,
+
+    #[argh(option, from_str_fn(parse_view))]
+    /// starting view of the TX console: text, mixed, hex or hexdump (default text)
+    pub tx_view: Option<View>,
+
+    #[argh(option, from_str_fn(parse_timestamp_mode))]
+    /// RX line timestamps: off, utc, session (time since start) or delta (time since the
+    /// previous line) (default off)
+    pub timestamps: Option<TimestampMode>,
+
+    #[argh(option)]
+    /// lines kept in each console, older ones are dropped (default 100000). At most
+    /// 16 MiB are kept whatever the number of lines
+    pub scrollback: Option<usize>,
+
+    #[argh(switch)]
+    /// interpret ANSI/VT100 escape sequences (colors, cursor movement) in received data
+    pub ansi: bool,
+
+    #[argh(switch)]
+    /// don't interpret ANSI escape sequences, even if the config file turns it on
+    pub no_ansi: bool,
+
+    #[argh(switch)]
+    /// open the port again when it comes back after a disconnect, e.g. a USB adapter
+    /// plugged back in
+    pub reconnect: bool,
+
+    #[argh(switch)]
+    /// don't reopen the port after a disconnect, even if the config file turns it on
+    pub no_reconnect: bool,
+
+    #[argh(switch)]
+    /// start in terminal mode: keys are sent as typed and RX is the only pane
+    pub terminal: bool,
+
+    #[argh(switch)]
+    /// don't start in terminal mode, even if the config file turns it on
+    pub no_terminal: bool,
+
+    #[argh(option, from_str_fn(parse_key))]
+    /// key leaving terminal mode, e.g. ctrl-a or f12 (default ctrl-])
+    pub escape_key: Option<KeyEvent>,
+
+    #[argh(switch)]
+    /// start in line mode: lines are edited locally and sent on Enter
+    pub line_mode: bool,
+
+    #[argh(switch)]
+    /// don't start in line mode, even if the config file turns it on
+    pub no_line_mode: bool,
+
+    #[argh(option)]
+    /// start logging the session to this file
+    pub log: Option<PathBuf>,
+
+    #[argh(option, from_str_fn(parse_log_format))]
+    /// log format: raw (RX bytes), text (RX as text) or tagged (timestamped RX and TX lines)
+    /// (default text)
+    pub log_format: Option<LogFormat>,
+
+    #[argh(option, from_str_fn(parse_size))]
+    /// rotate the log once it reaches this size (e.g. 10M), older parts are renamed to
+    /// <log>.1, <log>.2, ...
+    pub log_max_size: Option<u64>,
+
+    #[argh(option)]
+    /// profile of the config file to use, over its defaults
+    pub profile: Option<String>,
+
+    #[argh(option)]
+    /// config file (default $XDG_CONFIG_HOME/tuicom/config.toml)
+    pub config: Option<PathBuf>,
+
+    /// serial ports to connect to, each with its own TX and RX, picked interactively if
+    /// omitted
     #[argh(positional)]
-    pub port: String,
+    pub ports: Vec<String>,
+}
+
+impl Args {
+    /// Fills the settings not given on the command line from a config profile
+    pub fn apply(&mut self, p: &Profile) {
+        if self.ports.is_empty() {
+            self.ports = p.ports.clone().unwrap_or_default();
+        }
+        self.baud = self.baud.or(p.baud);
+        self.data_bits = self.data_bits.or(p.data_bits);
+        self.parity = self.parity.or(p.parity);
+        self.stop_bits = self.stop_bits.or(p.stop_bits);
+        self.flow_control = self.flow_control.or(p.flow_control);
+        self.dtr = self.dtr.or(p.dtr);
+        self.rts = self.rts.or(p.rts);
+        self.break_ms = self.break_ms.or(p.break_ms);
+        self.tx_eol = self.tx_eol.or(p.tx_eol);
+        self.rx_eol = self.rx_eol.or(p.rx_eol);
+        self.encoding = self.encoding.or(p.encoding);
+        self.control = self.control.or(p.control);
+        self.hex_escape = self.hex_escape.or(p.hex_escape);
+        self.dump_width = self.dump_width.or(p.dump_width);
+        self.rx_view = self.rx_view.or(p.rx_view);
+        self.tx_view = self.tx_view.or(p.tx_view);
+        self.timestamps = self.timestamps.or(p.timestamps);
+        self.scrollback = self.scrollback.or(p.scrollback);
+        self.ansi = switch(self.ansi, self.no_ansi, p.ansi);
+        self.terminal = switch(self.terminal, self.no_terminal, p.terminal);
+        self.line_mode = switch(self.line_mode, self.no_line_mode, p.line_mode);
+        self.reconnect = switch(self.reconnect, self.no_reconnect, p.reconnect);
+        self.log = self.log.take().or_else(|| p.log.clone());
+        self.log_format = self.log_format.or(p.log_format);
+        self.log_max_size = self.log_max_size.or(p.log_max_size);
+        self.escape_key = self.escape_key.or(p.escape_key);
+    }
+    /// Line settings, `None` without a baud rate
+    pub fn line_config(&self) -> Option<LineConfig> {
+        Some(LineConfig {
+            baud_rate: self.baud?,
+            data_bits: self.data_bits.unwrap_or(DataBits::Eight),
+            parity: self.parity.unwrap_or(Parity::None),
+            stop_bits: self.stop_bits.unwrap_or(StopBits::One),
+            flow_control: self.flow_control.unwrap_or(FlowControl::None),
+        })
+    }
+}
+
+/// Setting of a switch given as `--<name>` (`on`) or `--no-<name>` (`off`), falling back to
+/// the config file
+fn switch(on: bool, off: bool, config: Option<bool>) -> bool {
+    !off && (on || config.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::from_args(&["tuicom"], args).unwrap()
+    }
+
+    fn profile() -> Profile {
+        Profile {
+            ports: Some(vec![String::from("/dev/ttyUSB0")]),
+            baud: Some(9600),
+            rx_view: Some(View::Hex),
+            timestamps: Some(TimestampMode::Session),
+            ansi: Some(true),
+            reconnect: Some(false),
+            ..Profile::default()
+        }
+    }
+
+    #[test]
+    fn command_line_over_config() {
+        let mut a = args(&["-b", "115200", "--rx-view", "mixed", "/dev/ttyACM0"]);
+        a.apply(&profile());
+        assert_eq!(a.baud, Some(115200));
+        assert_eq!(a.rx_view, Some(View::Mixed));
+        assert_eq!(a.timestamps, Some(TimestampMode::Session));
+        assert_eq!(a.tx_view, None);
+        assert_eq!(a.ports, ["/dev/ttyACM0"]);
+
+        let mut a = args(&[]);
+        a.apply(&profile());
+        assert_eq!(a.baud, Some(9600));
+        assert_eq!(a.ports, ["/dev/ttyUSB0"]);
+    }
+
+    #[test]
+    fn switches() {
+        let mut a = args(&[]);
+        a.apply(&profile());
+        assert!(a.ansi);
+        assert!(!a.reconnect);
+        assert!(!a.terminal);
+
+        let mut a = args(&["--no-ansi", "--reconnect", "--terminal"]);
+        a.apply(&profile());
+        assert!(!a.ansi);
+        assert!(a.reconnect);
+        assert!(a.terminal);
+
+        // the `--no-` form wins when both are given
+        let mut a = args(&["--line-mode", "--no-line-mode"]);
+        a.apply(&Profile::default());
+        assert!(!a.line_mode);
+    }
 }
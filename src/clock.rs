@@ -0,0 +1,149 @@
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Broken down UTC wall clock time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millis: u32,
+}
+
+impl DateTime {
+    pub fn now() -> Self {
+        Self::from_system(SystemTime::now())
+    }
+    pub fn from_system(t: SystemTime) -> Self {
+        let since_epoch = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = since_epoch.as_secs() as i64;
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let secs_of_day = secs.rem_euclid(86400) as u32;
+        Self {
+            year,
+            month,
+            day,
+            hour: secs_of_day / 3600,
+            minute: secs_of_day / 60 % 60,
+            second: secs_of_day % 60,
+            millis: since_epoch.subsec_millis(),
+        }
+    }
+    /// Compact form usable in file names, e.g. `20230417-153000`
+    pub fn file_stamp(&self) -> String {
+        format!(
+            "{:04}{:02}{:02}-{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Formats as `2023-04-17 15:30:00.123`
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millis
+        )
+    }
+}
+
+/// Converts days since the unix epoch to a (year, month, day) date.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats a duration as `01:02:03.456`
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        d.subsec_millis()
+    )
+}
+
+/// How line timestamps are rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampMode {
+    #[default]
+    Off,
+    /// wall clock time (UTC)
+    Absolute,
+    /// time since the session started
+    Session,
+    /// time since the previous line
+    Delta,
+}
+
+impl TimestampMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Session,
+            Self::Session => Self::Delta,
+            Self::Delta => Self::Off,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Absolute => "UTC time",
+            Self::Session => "since start",
+            Self::Delta => "delta",
+        }
+    }
+    /// Prefix for a line received at `at`, `None` if timestamps are off
+    pub fn stamp(
+        self,
+        at: SystemTime,
+        started: SystemTime,
+        prev: Option<SystemTime>,
+    ) -> Option<String> {
+        let since = |t: SystemTime| at.duration_since(t).unwrap_or_default();
+        match self {
+            Self::Off => None,
+            Self::Absolute => {
+                let t = DateTime::from_system(at);
+                Some(format!(
+                    "[{:02}:{:02}:{:02}.{:03}] ",
+                    t.hour, t.minute, t.second, t.millis
+                ))
+            }
+            Self::Session => Some(format!("[{}] ", format_duration(since(started)))),
+            Self::Delta => Some(format!(
+                "[+{}] ",
+                format_duration(prev.map(since).unwrap_or_default())
+            )),
+        }
+    }
+}
+
+pub fn parse_timestamp_mode(s: &str) -> Result<TimestampMode, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "off" | "none" => Ok(TimestampMode::Off),
+        "utc" | "absolute" => Ok(TimestampMode::Absolute),
+        "session" | "start" => Ok(TimestampMode::Session),
+        "delta" => Ok(TimestampMode::Delta),
+        _ => Err(format!(
+            "invalid timestamps `{s}`, expected off, utc, session or delta"
+        )),
+    }
+}
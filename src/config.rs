@@ -0,0 +1,465 @@
+use crate::clock::{parse_timestamp_mode, TimestampMode};
+use crate::decode::{
+    parse_control_style, parse_encoding, parse_hex_escape, ControlStyle, Encoding, HexEscape,
+};
+use crate::keymap::Action;
+use crate::keys::parse_key;
+use crate::line_ending::{parse_line_ending, parse_rx_newline, LineEnding, RxNewline};
+use crate::logger::{parse_log_format, parse_size, LogFormat};
+use crate::macros::{self, Macro};
+use crate::screen::{parse_dump_width, parse_view, DumpWidth, View};
+use crate::send_file::expand_home;
+use crate::serial::{
+    parse_data_bits, parse_flow_control, parse_level, parse_parity, parse_stop_bits,
+};
+use crossterm::event::KeyEvent;
+use serialport::{DataBits, FlowControl, Parity, StopBits};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Settings of the config file, each overridden by the command line flag of the same name
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// ports to open, one or a list of them
+    pub ports: Option<Vec<String>>,
+    pub baud: Option<u32>,
+    pub data_bits: Option<DataBits>,
+    pub parity: Option<Parity>,
+    pub stop_bits: Option<StopBits>,
+    pub flow_control: Option<FlowControl>,
+    pub dtr: Option<bool>,
+    pub rts: Option<bool>,
+    pub break_ms: Option<u64>,
+    pub tx_eol: Option<LineEnding>,
+    pub rx_eol: Option<RxNewline>,
+    pub encoding: Option<Encoding>,
+    pub control: Option<ControlStyle>,
+    pub hex_escape: Option<HexEscape>,
+    pub dump_width: Option<DumpWidth>,
+    pub rx_view: Option<View>,
+    pub tx_view: Option<View>,
+    pub timestamps: Option<TimestampMode>,
+    pub scrollback: Option<usize>,
+    pub ansi: Option<bool>,
+    pub terminal: Option<bool>,
+    pub line_mode: Option<bool>,
+    pub reconnect: Option<bool>,
+    pub log: Option<PathBuf>,
+    pub log_format: Option<LogFormat>,
+    pub log_max_size: Option<u64>,
+    pub macros: Option<MacroSource>,
+    pub escape_key: Option<KeyEvent>,
+    /// actions rebound in the `[keys]` table, applied in order over the default keymap
+    pub keys: Vec<(Action, Vec<KeyEvent>)>,
+}
+
+/// Where the macros of a profile come from
+#[derive(Debug, Clone)]
+pub enum MacroSource {
+    /// a macro file, saved when macros are edited
+    File(PathBuf),
+    /// macros written in the config file, edits only last for the session
+    List(Vec<Macro>),
+}
+
+impl Profile {
+    /// Fills the settings left unset with the ones of `other`
+    fn or(self, other: Profile) -> Profile {
+        Profile {
+            ports: self.ports.or(other.ports),
+            baud: self.baud.or(other.baud),
+            data_bits: self.data_bits.or(other.data_bits),
+            parity: self.parity.or(other.parity),
+            stop_bits: self.stop_bits.or(other.stop_bits),
+            flow_control: self.flow_control.or(other.flow_control),
+            dtr: self.dtr.or(other.dtr),
+            rts: self.rts.or(other.rts),
+            break_ms: self.break_ms.or(other.break_ms),
+            tx_eol: self.tx_eol.or(other.tx_eol),
+            rx_eol: self.rx_eol.or(other.rx_eol),
+            encoding: self.encoding.or(other.encoding),
+            control: self.control.or(other.control),
+            hex_escape: self.hex_escape.or(other.hex_escape),
+            dump_width: self.dump_width.or(other.dump_width),
+            rx_view: self.rx_view.or(other.rx_view),
+            tx_view: self.tx_view.or(other.tx_view),
+            timestamps: self.timestamps.or(other.timestamps),
+            scrollback: self.scrollback.or(other.scrollback),
+            ansi: self.ansi.or(other.ansi),
+            terminal: self.terminal.or(other.terminal),
+            line_mode: self.line_mode.or(other.line_mode),
+            reconnect: self.reconnect.or(other.reconnect),
+            log: self.log.or(other.log),
+            log_format: self.log_format.or(other.log_format),
+            log_max_size: self.log_max_size.or(other.log_max_size),
+            macros: self.macros.or(other.macros),
+            escape_key: self.escape_key.or(other.escape_key),
+            keys: [other.keys, self.keys].concat(),
+        }
+    }
+
+    /// Reads the settings of a table, `context` naming it in errors
+    fn parse(table: &Table, context: &str, config_dir: &Path) -> Result<Self, String> {
+        let r = Reader { table, context };
+        let mut profile = Profile {
+            ports: r.strings("port")?,
+            baud: r.get("baud", |s| {
+                s.parse().map_err(|_| format!("invalid baud rate `{s}`"))
+            })?,
+            data_bits: r.get("data-bits", parse_data_bits)?,
+            parity: r.get("parity", parse_parity)?,
+            stop_bits: r.get("stop-bits", parse_stop_bits)?,
+            flow_control: r.get("flow-control", parse_flow_control)?,
+            dtr: r.get("dtr", parse_level)?,
+            rts: r.get("rts", parse_level)?,
+            break_ms: r.get("break-ms", |s| {
+                s.parse().map_err(|_| format!("invalid length `{s}`"))
+            })?,
+            tx_eol: r.get("tx-eol", parse_line_ending)?,
+            rx_eol: r.get("rx-eol", parse_rx_newline)?,
+            encoding: r.get("encoding", parse_encoding)?,
+            control: r.get("control", parse_control_style)?,
+            hex_escape: r.get("hex-escape", parse_hex_escape)?,
+            dump_width: r.get("dump-width", parse_dump_width)?,
+            rx_view: r.get("rx-view", parse_view)?,
+            tx_view: r.get("tx-view", parse_view)?,
+            timestamps: r.get("timestamps", parse_timestamp_mode)?,
+            scrollback: r.get("scrollback", |s| {
+                s.parse()
+                    .map_err(|_| format!("invalid number of lines `{s}`"))
+            })?,
+            ansi: r.flag("ansi")?,
+            terminal: r.flag("terminal")?,
+            line_mode: r.flag("line-mode")?,
+            reconnect: r.flag("reconnect")?,
+            log: r.get("log", |s| Ok(expand_home(s)))?,
+            log_format: r.get("log-format", parse_log_format)?,
+            log_max_size: r.get("log-max-size", parse_size)?,
+            macros: r.macros(config_dir)?,
+            escape_key: None,
+            keys: Vec::new(),
+        };
+        match table.get("keys") {
+            Some(Value::Table(keys)) => profile.parse_keys(keys, context)?,
+            Some(_) => return Err(format!("{context}keys: expected a table")),
+            None => (),
+        }
+        Ok(profile)
+    }
+
+    /// Reads the `[keys]` table: `escape` for terminal mode, and the keys of NORMAL mode
+    /// actions, one key or a list of them
+    fn parse_keys(&mut self, keys: &Table, context: &str) -> Result<(), String> {
+        let context = format!("{context}keys.");
+        for (name, value) in keys {
+            let err = |e: &str| format!("{context}{name}: {e}");
+            let list: Vec<KeyEvent> = match value {
+                Value::String(key) => vec![parse_key(key).map_err(|e| err(&e))?],
+                Value::Array(list) => list
+                    .iter()
+                    .map(|key| match key {
+                        Value::String(key) => parse_key(key).map_err(|e| err(&e)),
+                        _ => Err(err("expected a key or a list of keys")),
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(err("expected a key or a list of keys")),
+            };
+            if name == "escape" {
+                match list[..] {
+                    [key] => self.escape_key = Some(key),
+                    _ => return Err(err("expected a single key")),
+                }
+                continue;
+            }
+            let action = Action::parse(name).ok_or_else(|| err("unknown action"))?;
+            self.keys.push((action, list));
+        }
+        Ok(())
+    }
+}
+
+/// Keys of a profile table
+const PROFILE_KEYS: &[&str] = &[
+    "port",
+    "baud",
+    "data-bits",
+    "parity",
+    "stop-bits",
+    "flow-control",
+    "dtr",
+    "rts",
+    "break-ms",
+    "tx-eol",
+    "rx-eol",
+    "encoding",
+    "control",
+    "hex-escape",
+    "dump-width",
+    "rx-view",
+    "tx-view",
+    "timestamps",
+    "scrollback",
+    "ansi",
+    "terminal",
+    "line-mode",
+    "reconnect",
+    "log",
+    "log-format",
+    "log-max-size",
+    "macros",
+    "keys",
+];
+
+/// Values of a table, with errors naming the offending key
+struct Reader<'a> {
+    table: &'a Table,
+    context: &'a str,
+}
+
+impl Reader<'_> {
+    /// Value of `key`, written as on the command line. Numbers and booleans are taken as
+    /// their text.
+    fn get<T>(
+        &self,
+        key: &str,
+        parse: impl Fn(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, String> {
+        let text = match self.table.get(key) {
+            None => return Ok(None),
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Integer(n)) => n.to_string(),
+            Some(Value::Boolean(b)) => b.to_string(),
+            Some(_) => {
+                return Err(format!(
+                    "{}{key}: expected a string or a number",
+                    self.context
+                ))
+            }
+        };
+        parse(&text)
+            .map(Some)
+            .map_err(|e| format!("{}{key}: {e}", self.context))
+    }
+    /// Value of `key`, a string or a list of them
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, String> {
+        let err = || format!("{}{key}: expected a string or a list of them", self.context);
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(vec![s.clone()])),
+            Some(Value::Array(list)) => list
+                .iter()
+                .map(|v| v.as_str().map(String::from).ok_or_else(err))
+                .collect::<Result<_, _>>()
+                .map(Some),
+            Some(_) => Err(err()),
+        }
+    }
+    fn flag(&self, key: &str) -> Result<Option<bool>, String> {
+        match self.table.get(key) {
+            None => Ok(None),
+            Some(Value::Boolean(b)) => Ok(Some(*b)),
+            Some(_) => Err(format!("{}{key}: expected true or false", self.context)),
+        }
+    }
+    /// `macros`, either the path of a macro file, relative to the config directory, or
+    /// a list of `{ key, name, data }` tables
+    fn macros(&self, config_dir: &Path) -> Result<Option<MacroSource>, String> {
+        let err = |i: usize, e: String| format!("{}macros[{i}]: {e}", self.context);
+        let list = match self.table.get("macros") {
+            None => return Ok(None),
+            Some(Value::String(path)) => {
+                return Ok(Some(MacroSource::File(config_dir.join(expand_home(path)))))
+            }
+            Some(Value::Array(list)) => list,
+            Some(_) => {
+                return Err(format!(
+                    "{}macros: expected a file name or a list of macros",
+                    self.context
+                ))
+            }
+        };
+        let mut macros = Vec::new();
+        for (i, m) in list.iter().enumerate() {
+            let Value::Table(m) = m else {
+                return Err(err(i, String::from("expected a { key, name, data } table")));
+            };
+            let field = |name: &str| match m.get(name) {
+                Some(Value::String(s)) => Ok(s.clone()),
+                None if name == "key" => Ok(String::new()),
+                _ => Err(err(i, format!("expected a string `{name}`"))),
+            };
+            let m = Macro {
+                name: field("name")?,
+                key: macros::parse_key(&field("key")?).map_err(|e| err(i, e))?,
+                data: field("data")?,
+            };
+            m.bytes().map_err(|e| err(i, e))?;
+            macros.push(m);
+        }
+        Ok(Some(MacroSource::List(macros)))
+    }
+    /// Fails on keys outside of `known`, most likely typos
+    fn check_keys(&self, known: &[&str]) -> Result<(), String> {
+        match self.table.keys().find(|k| !known.contains(&k.as_str())) {
+            Some(k) => Err(format!("{}{k}: unknown setting", self.context)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Contents of the config file: defaults at the top level and named profiles in
+/// `[profile.<name>]` tables
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    defaults: Profile,
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// Loads the config file at `path`. A missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        };
+        Self::parse(&text, path.parent().unwrap_or(Path::new(".")))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+    fn parse(text: &str, config_dir: &Path) -> Result<Self, String> {
+        let table = text.parse::<Table>().map_err(|e| e.message().to_string())?;
+        let defaults = Profile::parse(&table, "", config_dir)?;
+        let known = [PROFILE_KEYS, &["profile"]].concat();
+        Reader {
+            table: &table,
+            context: "",
+        }
+        .check_keys(&known)?;
+        let mut profiles = BTreeMap::new();
+        match table.get("profile") {
+            Some(Value::Table(t)) => {
+                for (name, profile) in t {
+                    let context = format!("profile.{name}.");
+                    let Value::Table(profile) = profile else {
+                        return Err(format!("profile.{name}: expected a table"));
+                    };
+                    Reader {
+                        table: profile,
+                        context: &context,
+                    }
+                    .check_keys(PROFILE_KEYS)?;
+                    profiles.insert(name.clone(), Profile::parse(profile, &context, config_dir)?);
+                }
+            }
+            Some(_) => return Err(String::from("profile: expected a table")),
+            None => (),
+        }
+        Ok(Self { defaults, profiles })
+    }
+    /// Settings of the profile `name` over the defaults, or the defaults alone
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, String> {
+        let Some(name) = name else {
+            return Ok(self.defaults.clone());
+        };
+        match self.profiles.get(name) {
+            Some(p) => Ok(p.clone().or(self.defaults.clone())),
+            None if self.profiles.is_empty() => Err(format!("unknown profile `{name}`")),
+            None => Err(format!(
+                "unknown profile `{name}`, expected one of {}",
+                self.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"
+baud = 115200
+rx-view = "hex"
+ansi = true
+macros = "macros.txt"
+
+[keys]
+quit = "ctrl-q"
+
+[profile.board]
+port = ["/dev/ttyUSB0", "/dev/ttyUSB1"]
+baud = 9600
+timestamps = "delta"
+ansi = false
+
+[profile.board.keys]
+help = ["f1", "?"]
+escape = "ctrl-a"
+"#;
+
+    fn parse(text: &str) -> Result<Config, String> {
+        Config::parse(text, Path::new("/etc/tuicom"))
+    }
+
+    #[test]
+    fn profile_over_defaults() {
+        let config = parse(TEXT).unwrap();
+        let defaults = config.profile(None).unwrap();
+        assert_eq!(defaults.baud, Some(115200));
+        assert_eq!(defaults.ports, None);
+        assert_eq!(defaults.ansi, Some(true));
+
+        let board = config.profile(Some("board")).unwrap();
+        assert_eq!(board.ports.unwrap(), ["/dev/ttyUSB0", "/dev/ttyUSB1"]);
+        assert_eq!(board.baud, Some(9600));
+        assert_eq!(board.rx_view, Some(View::Hex));
+        assert_eq!(board.timestamps, Some(TimestampMode::Delta));
+        assert_eq!(board.ansi, Some(false));
+        assert!(
+            matches!(board.macros, Some(MacroSource::File(p)) if p == Path::new("/etc/tuicom/macros.txt"))
+        );
+        assert_eq!(board.escape_key, Some(parse_key("ctrl-a").unwrap()));
+        // the profile's bindings come after the defaults so they win
+        let actions = board
+            .keys
+            .iter()
+            .map(|(a, k)| (*a, k.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(actions, [(Action::Quit, 1), (Action::Help, 2)]);
+    }
+
+    #[test]
+    fn unknown_profile() {
+        let err = parse(TEXT).unwrap().profile(Some("bord")).unwrap_err();
+        assert_eq!(err, "unknown profile `bord`, expected one of board");
+        let err = parse("").unwrap().profile(Some("board")).unwrap_err();
+        assert_eq!(err, "unknown profile `board`");
+    }
+
+    #[test]
+    fn unknown_settings() {
+        assert_eq!(parse("bauds = 9600").unwrap_err(), "bauds: unknown setting");
+        assert_eq!(
+            parse("[profile.a]\nprofile = 'b'").unwrap_err(),
+            "profile.a.profile: unknown setting"
+        );
+        assert_eq!(
+            parse("[profile.a.keys]\nquiet = 'q'").unwrap_err(),
+            "profile.a.keys.quiet: unknown action"
+        );
+        assert_eq!(
+            parse("[keys]\nescape = ['ctrl-a', 'f12']").unwrap_err(),
+            "keys.escape: expected a single key"
+        );
+        assert_eq!(
+            parse("ansi = 'yes'").unwrap_err(),
+            "ansi: expected true or false"
+        );
+        assert!(parse("baud = 'fast'").unwrap_err().starts_with("baud: "));
+    }
+}
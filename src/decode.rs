@@ -0,0 +1,190 @@
+use std::fmt::Write;
+
+/// Text encoding of the bytes shown in ASCII mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    /// bytes above 0x7F are shown as invalid
+    Ascii,
+}
+
+impl Encoding {
+    pub const ALL: &'static [Encoding] = &[Self::Utf8, Self::Latin1, Self::Ascii];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::Latin1 => "Latin-1",
+            Self::Ascii => "ASCII",
+        }
+    }
+}
+
+pub fn parse_encoding(s: &str) -> Result<Encoding, String> {
+    match s.to_ascii_lowercase().replace('-', "").as_str() {
+        "utf8" => Ok(Encoding::Utf8),
+        "latin1" | "iso88591" => Ok(Encoding::Latin1),
+        "ascii" => Ok(Encoding::Ascii),
+        _ => Err(format!(
+            "invalid encoding `{s}`, expected utf8, latin1 or ascii"
+        )),
+    }
+}
+
+/// How control characters are made visible
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlStyle {
+    /// Unicode control pictures, e.g. `␛`
+    Picture,
+    /// e.g. `<0x1B>`
+    Hex,
+    /// caret notation, e.g. `^[`
+    Caret,
+}
+
+impl ControlStyle {
+    pub const ALL: &'static [ControlStyle] = &[Self::Picture, Self::Hex, Self::Caret];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Picture => "picture",
+            Self::Hex => "hex",
+            Self::Caret => "caret",
+        }
+    }
+}
+
+pub fn parse_control_style(s: &str) -> Result<ControlStyle, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "picture" | "pictures" => Ok(ControlStyle::Picture),
+        "hex" => Ok(ControlStyle::Hex),
+        "caret" => Ok(ControlStyle::Caret),
+        _ => Err(format!(
+            "invalid control style `{s}`, expected picture, hex or caret"
+        )),
+    }
+}
+
+/// How the mixed view writes non-printable bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HexEscape {
+    /// e.g. `<1B>`
+    Angle,
+    /// e.g. `\x1B`
+    Backslash,
+}
+
+impl HexEscape {
+    pub const ALL: &'static [HexEscape] = &[Self::Angle, Self::Backslash];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Angle => "<XX>",
+            Self::Backslash => "\\xXX",
+        }
+    }
+    pub fn push(self, byte: u8, out: &mut String) {
+        match self {
+            Self::Angle => write!(out, "<{byte:02X}>").unwrap(),
+            Self::Backslash => write!(out, "\\x{byte:02X}").unwrap(),
+        }
+    }
+}
+
+pub fn parse_hex_escape(s: &str) -> Result<HexEscape, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "angle" | "<xx>" => Ok(HexEscape::Angle),
+        "backslash" | "c" | "\\xxx" => Ok(HexEscape::Backslash),
+        _ => Err(format!(
+            "invalid hex escape `{s}`, expected angle (<1B>) or backslash (\\x1B)"
+        )),
+    }
+}
+
+/// Incremental byte to text decoder, keeping partial UTF-8 sequences between pushes
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    pub encoding: Encoding,
+    pub control: ControlStyle,
+    pending: Vec<u8>,
+    expected: usize,
+}
+
+impl Decoder {
+    pub fn new(encoding: Encoding, control: ControlStyle) -> Self {
+        Self {
+            encoding,
+            control,
+            pending: Vec::with_capacity(4),
+            expected: 0,
+        }
+    }
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.expected = 0;
+    }
+    /// Decodes one byte into `out`. Tabs, CR and LF are left to the caller.
+    pub fn push(&mut self, byte: u8, out: &mut String) {
+        match self.encoding {
+            Encoding::Latin1 => self.push_char(char::from(byte), out),
+            Encoding::Ascii if byte.is_ascii() => self.push_char(char::from(byte), out),
+            Encoding::Ascii => push_invalid(byte, out),
+            Encoding::Utf8 => self.push_utf8(byte, out),
+        }
+    }
+    /// Shows an incomplete UTF-8 sequence as invalid bytes
+    pub fn flush(&mut self, out: &mut String) {
+        for &b in &self.pending {
+            push_invalid(b, out);
+        }
+        self.reset();
+    }
+    fn push_utf8(&mut self, byte: u8, out: &mut String) {
+        if !self.pending.is_empty() {
+            if byte & 0xC0 == 0x80 {
+                self.pending.push(byte);
+                if self.pending.len() == self.expected {
+                    match std::str::from_utf8(&self.pending) {
+                        Ok(s) => {
+                            let c = s.chars().next().unwrap();
+                            self.reset();
+                            self.push_char(c, out);
+                        }
+                        Err(_) => self.flush(out),
+                    }
+                }
+                return;
+            }
+            // sequence cut short, the byte starts something new
+            self.flush(out);
+        }
+        self.expected = match byte {
+            0x00..=0x7F => return self.push_char(char::from(byte), out),
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return push_invalid(byte, out),
+        };
+        self.pending.push(byte);
+    }
+    fn push_char(&self, c: char, out: &mut String) {
+        if !c.is_control() {
+            out.push(c);
+            return;
+        }
+        let code = c as u32;
+        match self.control {
+            // C1 controls have no pictures or caret notation
+            _ if code > 0x7F => write!(out, "<0x{code:02X}>").unwrap(),
+            ControlStyle::Picture if code == 0x7F => out.push('\u{2421}'),
+            ControlStyle::Picture => out.push(char::from_u32(0x2400 + code).unwrap()),
+            ControlStyle::Hex => write!(out, "<0x{code:02X}>").unwrap(),
+            ControlStyle::Caret => {
+                out.push('^');
+                out.push(char::from((code as u8) ^ 0x40));
+            }
+        }
+    }
+}
+
+fn push_invalid(byte: u8, out: &mut String) {
+    write!(out, "<0x{byte:02X}>").unwrap();
+}
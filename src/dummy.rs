@@ -1,28 +1,44 @@
-use serialport::{DataBits, SerialPort};
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
 use std::collections::VecDeque;
 use std::io::{Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::Duration;
 
+/// Loopback port: what is written is read back, by every clone
 #[derive(Debug, Clone)]
 pub struct DummySerial {
-    buffer: VecDeque<u8>,
+    buffer: Arc<(Mutex<VecDeque<u8>>, Condvar)>,
+    timeout: Duration,
     baud_rate: u32,
     data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+    dtr: bool,
+    rts: bool,
 }
 
 impl DummySerial {
     pub fn new(baud_rate: u32) -> Self {
         Self {
-            buffer: VecDeque::new(),
+            buffer: Arc::default(),
+            timeout: Duration::from_millis(500),
             baud_rate,
             data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            dtr: true,
+            rts: true,
         }
     }
 }
 
 impl Write for DummySerial {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.buffer.extend(buf);
+        let (buffer, ready) = &*self.buffer;
+        buffer.lock().unwrap().extend(buf);
+        ready.notify_all();
         Ok(buf.len())
     }
     fn flush(&mut self) -> std::io::Result<()> {
@@ -30,16 +46,20 @@ impl Write for DummySerial {
     }
 }
 impl Read for DummySerial {
+    /// Waits up to the timeout for something to read, like a real port
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        let mut ctr = 0;
-        for i in buf {
-            match self.buffer.pop_front() {
-                Some(b) => *i = b,
-                None => break,
-            }
-            ctr += 1;
+        let (buffer, ready) = &*self.buffer;
+        let (mut buffer, res) = ready
+            .wait_timeout_while(buffer.lock().unwrap(), self.timeout, |b| b.is_empty())
+            .unwrap();
+        if res.timed_out() {
+            return Err(std::io::ErrorKind::TimedOut.into());
         }
-        Ok(ctr)
+        let n = buf.len().min(buffer.len());
+        for (i, b) in buffer.drain(..n).enumerate() {
+            buf[i] = b;
+        }
+        Ok(n)
     }
 }
 
@@ -62,16 +82,17 @@ impl SerialPort for DummySerial {
         Ok(())
     }
     fn timeout(&self) -> std::time::Duration {
-        Duration::from_millis(500)
+        self.timeout
     }
-    fn set_timeout(&mut self, _timeout: Duration) -> serialport::Result<()> {
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.timeout = timeout;
         Ok(())
     }
     fn clear(&self, _buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
         Ok(())
     }
     fn bytes_to_read(&self) -> serialport::Result<u32> {
-        Ok(self.buffer.len() as u32)
+        Ok(self.buffer.0.lock().unwrap().len() as u32)
     }
     fn bytes_to_write(&self) -> serialport::Result<u32> {
         Ok(0)
@@ -83,45 +104,51 @@ impl SerialPort for DummySerial {
         Ok(())
     }
     fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
-        Ok(serialport::FlowControl::Software)
+        Ok(self.flow_control)
     }
     fn set_flow_control(
         &mut self,
-        _flow_control: serialport::FlowControl,
+        flow_control: serialport::FlowControl,
     ) -> serialport::Result<()> {
+        self.flow_control = flow_control;
         Ok(())
     }
     fn parity(&self) -> serialport::Result<serialport::Parity> {
-        todo!()
+        Ok(self.parity)
     }
-    fn set_parity(&mut self, _parity: serialport::Parity) -> serialport::Result<()> {
-        todo!()
+    fn set_parity(&mut self, parity: serialport::Parity) -> serialport::Result<()> {
+        self.parity = parity;
+        Ok(())
     }
+    // the status lines are looped back like on a null modem cable
     fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
-        todo!()
+        Ok(self.dtr)
     }
     fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
-        todo!()
+        Ok(self.rts)
     }
     fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
-        todo!()
+        Ok(self.dtr)
     }
     fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
-        todo!()
+        Ok(false)
     }
-    fn set_stop_bits(&mut self, _stop_bits: serialport::StopBits) -> serialport::Result<()> {
-        todo!()
+    fn set_stop_bits(&mut self, stop_bits: serialport::StopBits) -> serialport::Result<()> {
+        self.stop_bits = stop_bits;
+        Ok(())
     }
     fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
-        todo!()
+        Ok(self.stop_bits)
     }
     fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
         Ok(Box::new(self.clone()))
     }
-    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
-        todo!()
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.dtr = level;
+        Ok(())
     }
-    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
-        todo!()
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.rts = level;
+        Ok(())
     }
 }
@@ -0,0 +1,83 @@
+/// Expands C-style escapes in typed text: `\r`, `\n`, `\t`, `\e` (ESC), `\\`, `\xNN` and
+/// octal `\N` to `\NNN` (`\0` is NUL). Other backslashes are kept as they are.
+pub fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes().peekable();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'r') => out.push(b'\r'),
+            Some(b'n') => out.push(b'\n'),
+            Some(b't') => out.push(b'\t'),
+            Some(d @ b'0'..=b'7') => {
+                let mut byte = u16::from(d - b'0');
+                for _ in 0..2 {
+                    match bytes.next_if(|b| (b'0'..=b'7').contains(b)) {
+                        Some(d) => byte = byte * 8 + u16::from(d - b'0'),
+                        None => break,
+                    }
+                }
+                match u8::try_from(byte) {
+                    Ok(byte) => out.push(byte),
+                    Err(_) => return Err(String::from("octal escapes go up to `\\377`")),
+                }
+            }
+            Some(b'e') => out.push(0x1B),
+            Some(b'\\') => out.push(b'\\'),
+            Some(b'x') => {
+                let digits = [bytes.next(), bytes.next()];
+                let hex = match digits {
+                    [Some(h), Some(l)] => std::str::from_utf8(&[h, l])
+                        .ok()
+                        .and_then(|d| u8::from_str_radix(d, 16).ok()),
+                    _ => None,
+                };
+                match hex {
+                    Some(byte) => out.push(byte),
+                    None => return Err(String::from("`\\x` must be followed by two hex digits")),
+                }
+            }
+            Some(other) => out.extend_from_slice(&[b'\\', other]),
+            None => {
+                return Err(String::from(
+                    "trailing `\\`, write `\\\\` to send a backslash",
+                ))
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_and_hex_escapes() {
+        assert_eq!(unescape(r"AT\r\n").unwrap(), b"AT\r\n");
+        assert_eq!(unescape(r"\eOP\t\\").unwrap(), b"\x1bOP\t\\");
+        assert_eq!(unescape(r"\x1A\xff\x00").unwrap(), [0x1A, 0xFF, 0]);
+        assert!(unescape(r"\x1").is_err());
+        assert!(unescape(r"\xzz").is_err());
+    }
+
+    #[test]
+    fn octal_escapes() {
+        assert_eq!(unescape(r"\0").unwrap(), [0]);
+        assert_eq!(unescape(r"\012\1").unwrap(), [b'\n', 1]);
+        // at most three digits are taken
+        assert_eq!(unescape(r"\1010").unwrap(), b"A0");
+        assert_eq!(unescape(r"\08").unwrap(), b"\08");
+        assert!(unescape(r"\400").is_err());
+    }
+
+    #[test]
+    fn unknown_and_trailing_backslashes() {
+        assert_eq!(unescape(r"C:\dir").unwrap(), br"C:\dir");
+        assert!(unescape(r"abc\").is_err());
+        assert_eq!(unescape(r"abc\\").unwrap(), br"abc\");
+    }
+}
@@ -0,0 +1,236 @@
+//! Serial and terminal I/O on background threads, feeding the UI loop through one channel
+
+use crate::transfer;
+use crossterm::event::KeyCode;
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Something for the UI loop to handle
+#[derive(Debug)]
+pub enum Event {
+    /// key press, resize... from the terminal
+    Input(crossterm::event::Event),
+    /// bytes read from a port
+    Rx(Tag, Vec<u8>),
+    /// reading or writing a port failed
+    PortError(Tag, io::Error),
+}
+
+/// Port an event comes from, and which run of its I/O threads sent it. Events of threads
+/// since restarted, e.g. on a port that was opened again, are stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tag {
+    /// index of the port
+    pub port: usize,
+    pub generation: u64,
+}
+
+pub fn channel() -> (Sender<Event>, Receiver<Event>) {
+    mpsc::channel()
+}
+
+/// Forwards terminal input to `events` until the UI loop goes away
+pub fn spawn_input(events: Sender<Event>) {
+    thread::spawn(move || {
+        while let Ok(ev) = crossterm::event::read() {
+            if events.send(Event::Input(ev)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Reader and writer threads of an open port. Writing queues the bytes for the writer
+/// thread, blocking only while a few chunks are already waiting.
+pub struct PortIo {
+    /// tag of the events of the threads
+    tag: Tag,
+    /// `None` once stopped
+    writer: Option<SyncSender<Vec<u8>>>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    events: Sender<Event>,
+}
+
+impl PortIo {
+    /// how long a read waits before checking whether to stop
+    const READ_TIMEOUT: Duration = Duration::from_millis(100);
+    const READ_SIZE: usize = 64 * 1024;
+    /// chunks queued for the writer thread before writes block
+    const WRITE_QUEUE: usize = 4;
+
+    /// Starts the threads on clones of `port`, sending what they read and their errors
+    /// to `events`, tagged with `index`
+    pub fn spawn(
+        port: &dyn SerialPort,
+        index: usize,
+        events: Sender<Event>,
+    ) -> serialport::Result<Self> {
+        let tag = Tag {
+            port: index,
+            generation: 0,
+        };
+        Self::start(port, tag, events)
+    }
+    fn start(port: &dyn SerialPort, tag: Tag, events: Sender<Event>) -> serialport::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut reader = port.try_clone()?;
+        reader.set_timeout(Self::READ_TIMEOUT)?;
+        let (reader_events, reader_stop) = (events.clone(), stop.clone());
+        let reader = thread::spawn(move || {
+            let mut buf = vec![0; Self::READ_SIZE];
+            while !reader_stop.load(Ordering::Relaxed) {
+                let ev = match reader.read(&mut buf) {
+                    // nothing, e.g. a pty without its other end
+                    Ok(0) => {
+                        thread::sleep(Self::READ_TIMEOUT);
+                        continue;
+                    }
+                    Ok(n) => Event::Rx(tag, buf[..n].to_vec()),
+                    Err(e)
+                        if matches!(
+                            e.kind(),
+                            io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+                        ) =>
+                    {
+                        continue
+                    }
+                    Err(e) => Event::PortError(tag, e),
+                };
+                // stopped while reading, what was read belongs to the port being replaced
+                if reader_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let error = matches!(ev, Event::PortError(..));
+                if reader_events.send(ev).is_err() || error {
+                    break;
+                }
+            }
+        });
+
+        let mut writer = port.try_clone()?;
+        let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(Self::WRITE_QUEUE);
+        let (writer_events, writer_stop) = (events.clone(), stop.clone());
+        let writer = thread::spawn(move || {
+            // ends once the `PortIo` and its sender are dropped
+            for data in rx {
+                if writer_stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                if let Err(e) = writer.write_all(&data) {
+                    if writer_events.send(Event::PortError(tag, e)).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            tag,
+            writer: Some(tx),
+            stop,
+            threads: vec![reader, writer],
+            events,
+        })
+    }
+    /// Stops the threads, waiting for them to let go of the port so that it can be opened
+    /// again. Their events still queued are stale once the threads are restarted.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.writer = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+    /// Stops the threads and starts new ones on `port`, e.g. once it is opened again after
+    /// a disconnect
+    pub fn restart(&mut self, port: &dyn SerialPort) -> serialport::Result<()> {
+        self.stop();
+        let tag = Tag {
+            generation: self.tag.generation + 1,
+            ..self.tag
+        };
+        *self = Self::start(port, tag, self.events.clone())?;
+        Ok(())
+    }
+    /// Tag of the events of the running threads
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+}
+
+impl Write for PortIo {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer
+            .as_ref()
+            .and_then(|w| w.send(buf.to_vec()).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "port writer stopped"))?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PortIo {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Port as seen by a file transfer, which takes over the UI loop's events until it ends.
+/// `Esc` cancels the transfer.
+pub struct TransferIo<'a> {
+    pub events: &'a Receiver<Event>,
+    pub port: &'a mut PortIo,
+    /// error of the port that ended the transfer, for the UI loop to handle afterwards
+    pub port_error: Option<io::Error>,
+    /// data and errors of the other ports, for the UI loop to handle afterwards
+    pub deferred: Vec<Event>,
+}
+
+impl transfer::Channel for TransferIo<'_> {
+    fn recv(&mut self, buf: &mut VecDeque<u8>, timeout: Duration) -> transfer::Result<()> {
+        let ev = if timeout.is_zero() {
+            match self.events.try_recv() {
+                Ok(ev) => ev,
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err(disconnected().into()),
+            }
+        } else {
+            match self.events.recv_timeout(timeout) {
+                Ok(ev) => ev,
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => return Err(disconnected().into()),
+            }
+        };
+        match ev {
+            Event::Rx(tag, data) if tag == self.port.tag() => buf.extend(data),
+            Event::Input(crossterm::event::Event::Key(k)) if k.code == KeyCode::Esc => {
+                return Err(transfer::Error::Cancelled)
+            }
+            Event::Input(_) => (),
+            Event::PortError(tag, e) if tag == self.port.tag() => {
+                let err = io::Error::new(e.kind(), e.to_string());
+                self.port_error = Some(e);
+                return Err(err.into());
+            }
+            ev => self.deferred.push(ev),
+        }
+        Ok(())
+    }
+    fn send(&mut self, data: &[u8]) -> transfer::Result<()> {
+        self.port.write_all(data)?;
+        Ok(())
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "port reader stopped")
+}
@@ -0,0 +1,301 @@
+use crate::keys;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Actions of NORMAL mode, bound to keys by the `Keymap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Help,
+    Quit,
+    TxView,
+    RxView,
+    RxAnsi,
+    ClearTx,
+    ClearRx,
+    TxLineEnding,
+    RxNewline,
+    ToggleDtr,
+    ToggleRts,
+    SendBreak,
+    SwitchPort,
+    AddPort,
+    NextPort,
+    PrevPort,
+    PortLayout,
+    Timeline,
+    BaudRate,
+    Settings,
+    ToggleLog,
+    Timestamps,
+    SwitchPane,
+    ScrollDown,
+    ScrollUp,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+    Top,
+    Bottom,
+    Search,
+    NextMatch,
+    PrevMatch,
+    Macros,
+    SendFile,
+    CancelSend,
+    Transfer,
+    InsertMode,
+    TerminalMode,
+    SendMode,
+}
+
+impl Action {
+    /// All actions, in the order of the bindings bar and the help popup
+    pub const ALL: &'static [Action] = &[
+        Self::Help,
+        Self::Quit,
+        Self::TxView,
+        Self::RxView,
+        Self::RxAnsi,
+        Self::ClearTx,
+        Self::ClearRx,
+        Self::TxLineEnding,
+        Self::RxNewline,
+        Self::ToggleDtr,
+        Self::ToggleRts,
+        Self::SendBreak,
+        Self::SwitchPort,
+        Self::AddPort,
+        Self::NextPort,
+        Self::PrevPort,
+        Self::PortLayout,
+        Self::Timeline,
+        Self::BaudRate,
+        Self::Settings,
+        Self::ToggleLog,
+        Self::Timestamps,
+        Self::SwitchPane,
+        Self::ScrollDown,
+        Self::ScrollUp,
+        Self::HalfPageDown,
+        Self::HalfPageUp,
+        Self::PageDown,
+        Self::PageUp,
+        Self::Top,
+        Self::Bottom,
+        Self::Search,
+        Self::NextMatch,
+        Self::PrevMatch,
+        Self::Macros,
+        Self::SendFile,
+        Self::CancelSend,
+        Self::Transfer,
+        Self::InsertMode,
+        Self::TerminalMode,
+        Self::SendMode,
+    ];
+
+    /// Name of the action in the `[keys]` table of the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Quit => "quit",
+            Self::TxView => "tx-view",
+            Self::RxView => "rx-view",
+            Self::RxAnsi => "rx-ansi",
+            Self::ClearTx => "clear-tx",
+            Self::ClearRx => "clear-rx",
+            Self::TxLineEnding => "tx-eol",
+            Self::RxNewline => "rx-eol",
+            Self::ToggleDtr => "toggle-dtr",
+            Self::ToggleRts => "toggle-rts",
+            Self::SendBreak => "send-break",
+            Self::SwitchPort => "switch-port",
+            Self::AddPort => "add-port",
+            Self::NextPort => "next-port",
+            Self::PrevPort => "prev-port",
+            Self::PortLayout => "port-layout",
+            Self::Timeline => "timeline",
+            Self::BaudRate => "baud-rate",
+            Self::Settings => "settings",
+            Self::ToggleLog => "toggle-log",
+            Self::Timestamps => "timestamps",
+            Self::SwitchPane => "switch-pane",
+            Self::ScrollDown => "scroll-down",
+            Self::ScrollUp => "scroll-up",
+            Self::HalfPageDown => "half-page-down",
+            Self::HalfPageUp => "half-page-up",
+            Self::PageDown => "page-down",
+            Self::PageUp => "page-up",
+            Self::Top => "top",
+            Self::Bottom => "bottom",
+            Self::Search => "search",
+            Self::NextMatch => "next-match",
+            Self::PrevMatch => "prev-match",
+            Self::Macros => "macros",
+            Self::SendFile => "send-file",
+            Self::CancelSend => "cancel-send",
+            Self::Transfer => "transfer",
+            Self::InsertMode => "insert-mode",
+            Self::TerminalMode => "terminal-mode",
+            Self::SendMode => "send-mode",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Help => "help",
+            Self::Quit => "quit",
+            Self::TxView => "TX view",
+            Self::RxView => "RX view",
+            Self::RxAnsi => "RX ANSI",
+            Self::ClearTx => "clear TX",
+            Self::ClearRx => "clear RX",
+            Self::TxLineEnding => "TX line ending",
+            Self::RxNewline => "RX newline",
+            Self::ToggleDtr => "toggle DTR",
+            Self::ToggleRts => "toggle RTS",
+            Self::SendBreak => "send BREAK",
+            Self::SwitchPort => "switch port",
+            Self::AddPort => "open another port",
+            Self::NextPort => "next port",
+            Self::PrevPort => "prev port",
+            Self::PortLayout => "tabs/tiles",
+            Self::Timeline => "merged timeline",
+            Self::BaudRate => "change baud rate",
+            Self::Settings => "serial settings",
+            Self::ToggleLog => "toggle log",
+            Self::Timestamps => "RX timestamps",
+            Self::SwitchPane => "switch pane",
+            Self::ScrollDown => "scroll down",
+            Self::ScrollUp => "scroll up",
+            Self::HalfPageDown => "half page down",
+            Self::HalfPageUp => "half page up",
+            Self::PageDown => "page down",
+            Self::PageUp => "page up",
+            Self::Top => "top",
+            Self::Bottom => "bottom, follow new data",
+            Self::Search => "search",
+            Self::NextMatch => "next match",
+            Self::PrevMatch => "prev match",
+            Self::Macros => "macros",
+            Self::SendFile => "send file",
+            Self::CancelSend => "cancel file send",
+            Self::Transfer => "XYZMODEM",
+            Self::InsertMode => "insert mode",
+            Self::TerminalMode => "terminal mode",
+            Self::SendMode => "char/line mode",
+        }
+    }
+
+    /// Whether the action is worth a place in the bindings bar, the help popup lists them all
+    pub fn in_bar(self) -> bool {
+        !matches!(
+            self,
+            Self::HalfPageDown
+                | Self::HalfPageUp
+                | Self::PageDown
+                | Self::PageUp
+                | Self::Top
+                | Self::Bottom
+                | Self::PrevMatch
+                | Self::PrevPort
+                | Self::CancelSend
+        )
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|a| a.name() == s)
+    }
+}
+
+/// Keys of the NORMAL mode actions
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(KeyEvent, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Action as A;
+        let char = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let bindings = vec![
+            (char('?'), A::Help),
+            (char('q'), A::Quit),
+            // Esc cancels a file send first, and quits otherwise
+            (key(KeyCode::Esc), A::CancelSend),
+            (key(KeyCode::Esc), A::Quit),
+            (char('H'), A::TxView),
+            (char('h'), A::RxView),
+            (char('a'), A::RxAnsi),
+            (char('C'), A::ClearTx),
+            (char('c'), A::ClearRx),
+            (char('l'), A::TxLineEnding),
+            (char('r'), A::RxNewline),
+            (char('d'), A::ToggleDtr),
+            (char('R'), A::ToggleRts),
+            (char('B'), A::SendBreak),
+            (char('p'), A::SwitchPort),
+            (char('P'), A::AddPort),
+            (char(']'), A::NextPort),
+            (char('['), A::PrevPort),
+            (char('w'), A::PortLayout),
+            (char('v'), A::Timeline),
+            (char('b'), A::BaudRate),
+            (char('s'), A::Settings),
+            (char('L'), A::ToggleLog),
+            (char('t'), A::Timestamps),
+            (key(KeyCode::Tab), A::SwitchPane),
+            (char('j'), A::ScrollDown),
+            (key(KeyCode::Down), A::ScrollDown),
+            (char('k'), A::ScrollUp),
+            (key(KeyCode::Up), A::ScrollUp),
+            (ctrl('d'), A::HalfPageDown),
+            (ctrl('u'), A::HalfPageUp),
+            (key(KeyCode::PageDown), A::PageDown),
+            (key(KeyCode::PageUp), A::PageUp),
+            (char('g'), A::Top),
+            (char('G'), A::Bottom),
+            (char('/'), A::Search),
+            (char('n'), A::NextMatch),
+            (char('N'), A::PrevMatch),
+            (char('m'), A::Macros),
+            (char('f'), A::SendFile),
+            (char('z'), A::Transfer),
+            (char('i'), A::InsertMode),
+            (char('T'), A::TerminalMode),
+            (char('e'), A::SendMode),
+        ];
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Actions bound to `key`, the first one that applies runs
+    pub fn actions(&self, key: KeyEvent) -> impl Iterator<Item = Action> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(k, _)| keys::is_key(key, *k))
+            .map(|(_, a)| *a)
+    }
+    /// Keys bound to `action`, in the order they were bound
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = KeyEvent> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, a)| *a == action)
+            .map(|(k, _)| *k)
+    }
+    /// Keys of `action` joined for display, e.g. `j/Down`
+    pub fn key_names(&self, action: Action) -> String {
+        self.keys(action)
+            .map(keys::key_name)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+    /// Binds `action` to `keys` only, taking the keys from the actions they were bound to
+    pub fn bind(&mut self, action: Action, keys: &[KeyEvent]) {
+        self.bindings
+            .retain(|(k, a)| *a != action && !keys.iter().any(|key| keys::is_key(*k, *key)));
+        self.bindings.extend(keys.iter().map(|&k| (k, action)));
+    }
+}
@@ -0,0 +1,149 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Parses a key such as `ctrl-]`, `alt-x`, `f12` or `esc`
+pub fn parse_key(s: &str) -> Result<KeyEvent, String> {
+    let err = || format!("invalid key `{s}`, expected e.g. ctrl-], alt-x or f12");
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = s;
+    // the key itself may be `-`, so only split while a modifier is left of it
+    while let Some((m, r)) = rest.split_once(['-', '+']).filter(|(_, r)| !r.is_empty()) {
+        modifiers |= match m.to_ascii_lowercase().as_str() {
+            "c" | "ctrl" | "control" => KeyModifiers::CONTROL,
+            "a" | "m" | "alt" | "meta" => KeyModifiers::ALT,
+            "s" | "shift" => KeyModifiers::SHIFT,
+            _ => return Err(err()),
+        };
+        rest = r;
+    }
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "esc" | "escape" => KeyCode::Esc,
+        "enter" | "return" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" | "bs" => KeyCode::Backspace,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        "insert" | "ins" => KeyCode::Insert,
+        "delete" | "del" => KeyCode::Delete,
+        f if f.len() > 1 && f.starts_with('f') => match f[1..].parse() {
+            Ok(n @ 1..=12) => KeyCode::F(n),
+            _ => return Err(err()),
+        },
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(err()),
+            }
+        }
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+/// Human readable name of a key, e.g. `Ctrl-]`
+pub fn key_name(key: KeyEvent) -> String {
+    let mut name = String::new();
+    for (m, prefix) in [
+        (KeyModifiers::CONTROL, "Ctrl-"),
+        (KeyModifiers::ALT, "Alt-"),
+        (KeyModifiers::SHIFT, "Shift-"),
+    ] {
+        if key.modifiers.contains(m) {
+            name.push_str(prefix);
+        }
+    }
+    match key.code {
+        KeyCode::Char(' ') => name.push_str("Space"),
+        KeyCode::Char(c) => name.push(c),
+        KeyCode::F(n) => name.push_str(&format!("F{n}")),
+        KeyCode::Esc => name.push_str("Esc"),
+        KeyCode::PageUp => name.push_str("PageUp"),
+        KeyCode::PageDown => name.push_str("PageDown"),
+        code => name.push_str(&format!("{code:?}")),
+    }
+    name
+}
+
+/// Whether `event` is `key`. Keys sending the same bytes are the same key, since terminals
+/// report e.g. `Ctrl-]` as `Ctrl-5`.
+pub fn is_key(event: KeyEvent, key: KeyEvent) -> bool {
+    if event.code == key.code && event.modifiers == key.modifiers {
+        return true;
+    }
+    let bytes = key_bytes(key, false);
+    !bytes.is_empty() && key_bytes(event, false) == bytes
+}
+
+/// Bytes a VT220/xterm style terminal sends for `key`. `app_cursor` selects the
+/// application cursor key sequences (`ESC O A` instead of `ESC [ A`).
+pub fn key_bytes(key: KeyEvent, app_cursor: bool) -> Vec<u8> {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+    // xterm modifier parameter of cursor and editing keys
+    let m = 1 + shift as u8 + 2 * alt as u8 + 4 * ctrl as u8;
+    let cursor = |c: char| match (m, app_cursor) {
+        (1, true) => format!("\x1bO{c}").into_bytes(),
+        (1, false) => format!("\x1b[{c}").into_bytes(),
+        _ => format!("\x1b[1;{m}{c}").into_bytes(),
+    };
+    let tilde = |n: u8| match m {
+        1 => format!("\x1b[{n}~").into_bytes(),
+        _ => format!("\x1b[{n};{m}~").into_bytes(),
+    };
+    let mut bytes = match key.code {
+        KeyCode::Char(c) if ctrl => match control_byte(c) {
+            Some(b) => vec![b],
+            None => return Vec::new(),
+        },
+        KeyCode::Char(c) => c.to_string().into_bytes(),
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => return b"\x1b[Z".to_vec(),
+        KeyCode::Backspace if ctrl => vec![0x08],
+        KeyCode::Backspace => vec![0x7F],
+        KeyCode::Esc => vec![0x1B],
+        KeyCode::Up => return cursor('A'),
+        KeyCode::Down => return cursor('B'),
+        KeyCode::Right => return cursor('C'),
+        KeyCode::Left => return cursor('D'),
+        KeyCode::Home => return cursor('H'),
+        KeyCode::End => return cursor('F'),
+        KeyCode::Insert => return tilde(2),
+        KeyCode::Delete => return tilde(3),
+        KeyCode::PageUp => return tilde(5),
+        KeyCode::PageDown => return tilde(6),
+        KeyCode::F(n @ 1..=4) => match m {
+            1 => return format!("\x1bO{}", char::from(b'O' + n)).into_bytes(),
+            _ => return format!("\x1b[1;{m}{}", char::from(b'O' + n)).into_bytes(),
+        },
+        KeyCode::F(n @ 5..=12) => return tilde([15, 17, 18, 19, 20, 21, 23, 24][n as usize - 5]),
+        _ => return Vec::new(),
+    };
+    // Alt sends an ESC prefix
+    if alt {
+        bytes.insert(0, 0x1B);
+    }
+    bytes
+}
+
+/// Control character sent for Ctrl-`c`, following xterm (`Ctrl-2` is NUL, `Ctrl-5` is `^]`...)
+pub fn control_byte(c: char) -> Option<u8> {
+    match c.to_ascii_lowercase() {
+        c @ ('@' | 'a'..='z' | '[' | '\\' | ']' | '^' | '_') => {
+            Some(c.to_ascii_uppercase() as u8 & 0x1F)
+        }
+        ' ' | '2' => Some(0x00),
+        '3' => Some(0x1B),
+        c @ '4'..='7' => Some(c as u8 - b'4' + 0x1C),
+        '8' | '?' => Some(0x7F),
+        '/' => Some(0x1F),
+        _ => None,
+    }
+}
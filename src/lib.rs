@@ -1,13 +1,44 @@
 pub mod app;
 pub mod args;
+pub mod clock;
+pub mod config;
+pub mod decode;
 pub mod dummy;
+pub mod escape;
+pub mod events;
+pub mod keymap;
+pub mod keys;
+pub mod line_edit;
+pub mod line_ending;
+pub mod logger;
+pub mod macros;
+pub mod paths;
+pub mod picker;
 pub mod screen;
+pub mod send_file;
+pub mod serial;
+pub mod session;
+pub mod timeline;
+pub mod transfer;
 pub mod ui;
+pub mod vt;
+pub mod xmodem;
+pub mod zmodem;
 
 use app::App;
 use args::Args;
+use config::{Config, MacroSource};
+use events::{Event, PortIo, TransferIo};
+use line_edit::SendMode;
+use logger::{LogFormat, Logger};
+use macros::Macros;
+use picker::{Pick, PortPicker};
+use serial::PortId;
+use session::Session;
 use std::io;
+use std::sync::mpsc::RecvTimeoutError;
 use std::time::Duration;
+use transfer::{Progress, Request, Summary};
 
 use ratatui::{
     backend::{Backend, CrosstermBackend},
@@ -28,35 +59,180 @@ pub enum Error {
     InvalidBaudRate(u16),
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("config error: {0}")]
+    Config(String),
+    #[error("no baud rate, give one with --baud or in the config file")]
+    NoBaudRate,
 }
 type Result<T> = std::result::Result<T, Error>;
 
 /// Application entry point
 pub fn run_app() -> Result<()> {
-    let args: Args = argh::from_env();
+    let mut args: Args = argh::from_env();
 
-    let port = if args.port == "dummy" {
-        Box::new(dummy::DummySerial::new(args.baud))
-    } else {
-        serialport::new(&args.port, args.baud)
-            .timeout(Duration::from_millis(500))
-            .open()?
+    let config = match args.config.clone() {
+        Some(path) if !path.exists() => {
+            return Err(Error::Config(format!("{}: no such file", path.display())))
+        }
+        Some(path) => Config::load(&path),
+        None => match paths::config_dir() {
+            Some(dir) => Config::load(&dir.join("config.toml")),
+            None => Ok(Config::default()),
+        },
     };
+    let profile = config
+        .and_then(|c| c.profile(args.profile.as_deref()))
+        .map_err(Error::Config)?;
+    args.apply(&profile);
+    let line = args.line_config().ok_or(Error::NoBaudRate)?;
+
     let mut terminal = start_tui()?;
     // little trick to replace `try` block
     let res = (|| {
-        let mut app = App::new(port);
+        let port_names = match args.ports.is_empty() {
+            false => args.ports.clone(),
+            true => match pick_port(&mut terminal)? {
+                Some(p) => vec![p],
+                None => return Ok(()),
+            },
+        };
+        let log_format = args.log_format.unwrap_or(LogFormat::Text);
+        let (events_tx, events) = events::channel();
+        let mut sessions = Vec::new();
+        for (i, name) in port_names.iter().enumerate() {
+            let mut port = serial::open(name, &line)?;
+            // as early as possible, to keep the pulse on open short
+            if let Some(dtr) = args.dtr {
+                port.write_data_terminal_ready(dtr)?;
+            }
+            if let Some(rts) = args.rts {
+                port.write_request_to_send(rts)?;
+            }
+            let mut logger = Logger::new(args.log.clone(), log_format, args.log_max_size);
+            // the other ports log next to the first one
+            if i > 0 {
+                logger = logger.for_port(session::port_name(name));
+            }
+            if args.log.is_some() {
+                logger.start()?;
+            }
+            let io = PortIo::spawn(port.as_ref(), i, events_tx.clone())?;
+            sessions.push(Session::new(port, PortId::of(name), io, line, logger));
+        }
+        events::spawn_input(events_tx.clone());
+        let mut app = App::new(sessions, events_tx);
+        app.reconnect = args.reconnect;
+        if let Some(ms) = args.break_ms {
+            app.break_duration = Duration::from_millis(ms);
+        }
+        for s in &mut app.sessions {
+            s.dtr = args.dtr.unwrap_or(true);
+            s.rts = args.rts.unwrap_or(true);
+            if let Some(eol) = args.tx_eol {
+                s.tx.line_ending = eol;
+            }
+            if let Some(eol) = args.rx_eol {
+                s.rx.display.set_newline(eol);
+            }
+            for display in [&mut s.tx.display, &mut s.rx.display] {
+                if let Some(encoding) = args.encoding {
+                    display.set_encoding(encoding);
+                }
+                if let Some(control) = args.control {
+                    display.set_control_style(control);
+                }
+                if let Some(escape) = args.hex_escape {
+                    display.set_hex_escape(escape);
+                }
+                if let Some(width) = args.dump_width {
+                    display.dump_width = width;
+                }
+                if let Some(lines) = args.scrollback {
+                    display.scrollback = lines;
+                }
+            }
+            if let Some(view) = args.rx_view {
+                s.rx.display.set_view(view);
+            }
+            if let Some(view) = args.tx_view {
+                s.tx.display.set_view(view);
+            }
+            if let Some(mode) = args.timestamps {
+                s.rx.display.timestamps = mode;
+                s.logger.timestamps = mode;
+            }
+            if args.ansi {
+                s.rx.switch_ansi();
+            }
+        }
+        if let Some(lines) = args.scrollback {
+            app.timeline.display.scrollback = lines;
+        }
+        if args.line_mode {
+            app.send_mode = SendMode::Line;
+        }
+        let (macros, error) = match profile.macros {
+            Some(MacroSource::List(list)) => (Macros::from_list(list), None),
+            Some(MacroSource::File(path)) => Macros::load(Some(path)),
+            None => Macros::load(paths::config_dir().map(|d| d.join("macros"))),
+        };
+        app.macros = macros;
+        if let Some(e) = error {
+            app.notify_error(e);
+        }
+        for (action, keys) in &profile.keys {
+            app.keymap.bind(*action, keys);
+        }
+        if let Some(key) = args.escape_key {
+            app.escape_key = key;
+        }
+        if args.terminal {
+            app.enter_terminal();
+        }
 
-        loop {
-            let ev = if event::poll(Duration::from_millis(1000 / 60))? {
-                Some(event::read()?)
-            } else {
-                None
+        terminal.draw(|f| ui::draw(f, &mut app))?;
+        'run: loop {
+            let mut redraw = match events.recv_timeout(app.next_tick()) {
+                Ok(ev) => {
+                    if app.update(ev)?.exit() {
+                        break;
+                    }
+                    // catch up on everything pending before drawing again
+                    while let Ok(ev) = events.try_recv() {
+                        if app.update(ev)?.exit() {
+                            break 'run;
+                        }
+                    }
+                    true
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => break,
             };
-            if app.update(ev)?.exit() {
-                break;
+            redraw |= app.tick()?;
+            if let Some(request) = app.transfer.take() {
+                let mut io = TransferIo {
+                    events: &events,
+                    port: &mut app.session_mut().io,
+                    port_error: None,
+                    deferred: Vec::new(),
+                };
+                let res = run_transfer(&mut terminal, &mut io, &request);
+                let (port_error, deferred) = (io.port_error, io.deferred);
+                if let Some(e) = port_error {
+                    app.update(Event::PortError(app.session().io.tag(), e))?;
+                }
+                app.transfer_done(&request, res);
+                for ev in deferred {
+                    app.update(ev)?;
+                }
+                redraw = true;
             }
-            terminal.draw(|f| ui::draw(f, &mut app))?;
+            if redraw {
+                terminal.draw(|f| ui::draw(f, &mut app))?;
+            }
+        }
+        for s in &mut app.sessions {
+            s.logger.stop()?;
         }
         Ok(())
     })();
@@ -64,6 +240,33 @@ pub fn run_app() -> Result<()> {
     res
 }
 
+/// Runs the port picker screen until a port is chosen or the user cancels
+fn pick_port<B: Backend>(terminal: &mut Terminal<B>) -> Result<Option<String>> {
+    let mut picker = PortPicker::new();
+    loop {
+        terminal.draw(|f| ui::draw_port_picker(f, &picker, f.size()))?;
+        if let event::Event::Key(k) = event::read()? {
+            match picker.handle_key(k) {
+                Pick::None => (),
+                Pick::Cancel => return Ok(None),
+                Pick::Selected(p) => return Ok(Some(p)),
+            }
+        }
+    }
+}
+
+/// Runs a file transfer on the port, showing its progress until it ends or Esc cancels it
+fn run_transfer<B: Backend>(
+    terminal: &mut Terminal<B>,
+    io: &mut TransferIo,
+    request: &Request,
+) -> transfer::Result<Summary> {
+    let mut report = |progress: &Progress| {
+        let _ = terminal.draw(|f| ui::draw_transfer(f, request, progress));
+    };
+    transfer::run(io, request, &mut report)
+}
+
 fn start_tui() -> Result<Terminal<CrosstermBackend<io::Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
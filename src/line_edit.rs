@@ -0,0 +1,228 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// How typed text reaches the port in INSERT mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendMode {
+    /// every keystroke is sent immediately
+    Char,
+    /// a line is edited locally and sent on Enter
+    Line,
+}
+
+/// Local command line with cursor movement and history
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    /// entry being browsed, `None` while editing a new line
+    hist_pos: Option<usize>,
+    /// new line stashed while browsing the history
+    draft: Vec<char>,
+    history_file: Option<PathBuf>,
+    /// lines in the history file, rewritten with the kept history once it has too many
+    file_lines: usize,
+}
+
+impl LineEditor {
+    const HISTORY_LEN: usize = 1000;
+
+    /// Creates an editor, loading the history from `history_file` if it exists
+    pub fn new(history_file: Option<PathBuf>) -> Self {
+        let mut history = history_file
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|s| s.lines().map(String::from).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let file_lines = history.len();
+        if history.len() > Self::HISTORY_LEN {
+            history.drain(..history.len() - Self::HISTORY_LEN);
+        }
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            history,
+            hist_pos: None,
+            draft: Vec::new(),
+            history_file,
+            file_lines,
+        }
+    }
+    pub fn line(&self) -> String {
+        self.line.iter().collect()
+    }
+    /// Cursor position, in characters
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+    /// Handles an editing key, returning the line once Enter is pressed
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<String> {
+        use KeyCode as K;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            K::Enter => return Some(self.submit()),
+            K::Left if ctrl => self.cursor = self.word_start(),
+            K::Right if ctrl => self.cursor = self.word_end(),
+            K::Left => self.cursor = self.cursor.saturating_sub(1),
+            K::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            K::Home => self.cursor = 0,
+            K::End => self.cursor = self.line.len(),
+            K::Up => self.history_prev(),
+            K::Down => self.history_next(),
+            K::Backspace if ctrl || alt => self.delete_word(),
+            K::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            K::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            K::Char('a') if ctrl => self.cursor = 0,
+            K::Char('e') if ctrl => self.cursor = self.line.len(),
+            K::Char('b') if ctrl => self.cursor = self.cursor.saturating_sub(1),
+            K::Char('f') if ctrl => self.cursor = (self.cursor + 1).min(self.line.len()),
+            K::Char('w') if ctrl => self.delete_word(),
+            K::Char('u') if ctrl => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            K::Char('k') if ctrl => self.line.truncate(self.cursor),
+            K::Char('b') if alt => self.cursor = self.word_start(),
+            K::Char('f') if alt => self.cursor = self.word_end(),
+            K::Char(_) if ctrl || alt => (),
+            K::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            K::Tab => {
+                self.line.insert(self.cursor, '\t');
+                self.cursor += 1;
+            }
+            _ => (),
+        }
+        None
+    }
+
+    fn submit(&mut self) -> String {
+        let line = self.line();
+        self.line.clear();
+        self.draft.clear();
+        self.cursor = 0;
+        self.hist_pos = None;
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > Self::HISTORY_LEN {
+                self.history.remove(0);
+            }
+            // history is a convenience, failing to persist it must not
+            // prevent the line from being sent
+            let _ = self.save_entry(&line);
+        }
+        line
+    }
+    fn save_entry(&mut self, line: &str) -> io::Result<()> {
+        let Some(path) = &self.history_file else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if self.file_lines >= Self::HISTORY_LEN {
+            // replaced in one go, so that a crash can't leave it half written
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(".tmp");
+            let text = self
+                .history
+                .iter()
+                .map(|l| format!("{l}\n"))
+                .collect::<String>();
+            fs::write(&tmp, text)?;
+            fs::rename(&tmp, path)?;
+            self.file_lines = self.history.len();
+            return Ok(());
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{line}")?;
+        self.file_lines += 1;
+        Ok(())
+    }
+    fn history_prev(&mut self) {
+        let pos = match self.hist_pos {
+            Some(0) => return,
+            Some(p) => p - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = std::mem::take(&mut self.line);
+                self.history.len() - 1
+            }
+        };
+        self.hist_pos = Some(pos);
+        self.line = self.history[pos].chars().collect();
+        self.cursor = self.line.len();
+    }
+    fn history_next(&mut self) {
+        let Some(pos) = self.hist_pos else { return };
+        if pos + 1 < self.history.len() {
+            self.hist_pos = Some(pos + 1);
+            self.line = self.history[pos + 1].chars().collect();
+        } else {
+            self.hist_pos = None;
+            self.line = std::mem::take(&mut self.draft);
+        }
+        self.cursor = self.line.len();
+    }
+    /// Start of the word before the cursor
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.line[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        while i > 0 && !self.line[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        i
+    }
+    /// End of the word after the cursor
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.line.len() && self.line[i].is_whitespace() {
+            i += 1;
+        }
+        while i < self.line.len() && !self.line[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    }
+    fn delete_word(&mut self) {
+        let start = self.word_start();
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_file_stays_bounded() {
+        let dir = std::env::temp_dir().join(format!("tuicom-{}-history", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("history");
+        let mut editor = LineEditor::new(Some(path.clone()));
+        for i in 0..LineEditor::HISTORY_LEN + 10 {
+            editor.line = format!("line {i}").chars().collect();
+            editor.submit();
+        }
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), LineEditor::HISTORY_LEN);
+        assert_eq!(text.lines().next(), Some("line 10"));
+        let editor = LineEditor::new(Some(path));
+        assert_eq!(editor.history.len(), LineEditor::HISTORY_LEN);
+        assert_eq!(editor.history.last().unwrap(), "line 1009");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,90 @@
+/// Line terminator sent when Enter is pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    None,
+    Cr,
+    Lf,
+    CrLf,
+    LfCr,
+}
+
+impl LineEnding {
+    pub const ALL: &'static [LineEnding] =
+        &[Self::None, Self::Cr, Self::Lf, Self::CrLf, Self::LfCr];
+    pub fn bytes(self) -> &'static [u8] {
+        match self {
+            Self::None => b"",
+            Self::Cr => b"\r",
+            Self::Lf => b"\n",
+            Self::CrLf => b"\r\n",
+            Self::LfCr => b"\n\r",
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Cr => "CR",
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+            Self::LfCr => "LFCR",
+        }
+    }
+}
+
+pub fn parse_line_ending(s: &str) -> Result<LineEnding, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "none" => Ok(LineEnding::None),
+        "cr" => Ok(LineEnding::Cr),
+        "lf" => Ok(LineEnding::Lf),
+        "crlf" => Ok(LineEnding::CrLf),
+        "lfcr" => Ok(LineEnding::LfCr),
+        _ => Err(format!(
+            "invalid line ending `{s}`, expected none, cr, lf, crlf or lfcr"
+        )),
+    }
+}
+
+/// Which received bytes break lines in the ASCII display
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxNewline {
+    /// LF breaks lines, CR is hidden
+    Lf,
+    /// CR breaks lines, LF is hidden
+    Cr,
+    /// CR, LF and CRLF each break a line
+    Any,
+}
+
+impl RxNewline {
+    pub const ALL: &'static [RxNewline] = &[Self::Lf, Self::Cr, Self::Any];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::Cr => "CR",
+            Self::Any => "any",
+        }
+    }
+    /// Whether `byte` breaks the line and whether it is hidden from the text, `after_cr`
+    /// telling whether it follows a CR
+    pub fn classify(self, byte: u8, after_cr: bool) -> (bool, bool) {
+        match (self, byte) {
+            (Self::Lf, b'\n') | (Self::Cr, b'\r') => (true, false),
+            (Self::Lf, b'\r') | (Self::Cr, b'\n') => (false, true),
+            (Self::Any, b'\r') => (true, false),
+            (Self::Any, b'\n') if after_cr => (false, true),
+            (Self::Any, b'\n') => (true, false),
+            _ => (false, false),
+        }
+    }
+}
+
+pub fn parse_rx_newline(s: &str) -> Result<RxNewline, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "lf" => Ok(RxNewline::Lf),
+        "cr" => Ok(RxNewline::Cr),
+        "any" | "crlf" => Ok(RxNewline::Any),
+        _ => Err(format!(
+            "invalid receive line ending `{s}`, expected lf, cr or any"
+        )),
+    }
+}
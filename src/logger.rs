@@ -0,0 +1,290 @@
+use crate::clock::{DateTime, TimestampMode};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// On-disk format of the session log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// received bytes, verbatim
+    Raw,
+    /// received data as text, with control bytes escaped and
+    /// lines prefixed like the RX timestamps
+    Text,
+    /// both directions, one timestamped and tagged line per data line
+    Tagged,
+}
+
+impl LogFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::Text => "text",
+            Self::Tagged => "tagged",
+        }
+    }
+}
+
+pub fn parse_log_format(s: &str) -> Result<LogFormat, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "raw" | "bin" | "binary" => Ok(LogFormat::Raw),
+        "text" | "txt" => Ok(LogFormat::Text),
+        "tagged" => Ok(LogFormat::Tagged),
+        _ => Err(format!(
+            "invalid log format `{s}`, expected raw, text or tagged"
+        )),
+    }
+}
+
+/// Parses a byte size with an optional K, M or G suffix
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (num, mult) = match s.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&s[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&s[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&s[..i], 1 << 30),
+        _ => (s, 1),
+    };
+    match num.parse::<u64>() {
+        Ok(0) => Err(String::from("the size can't be 0")),
+        Ok(n) => n
+            .checked_mul(mult)
+            .ok_or_else(|| format!("size `{s}` is too large")),
+        Err(_) => Err(format!("invalid size `{s}`")),
+    }
+}
+
+/// Direction of the traffic being logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+impl Direction {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Rx => "RX",
+            Self::Tx => "TX",
+        }
+    }
+}
+
+/// Streams session traffic to a file, rotating it once it grows past `max_size`
+pub struct Logger {
+    /// path given by the user, a timestamped name is generated if `None`
+    path: Option<PathBuf>,
+    /// port named in generated file names, to tell the logs of several ports apart
+    port: Option<String>,
+    pub format: LogFormat,
+    max_size: Option<u64>,
+    file: Option<(PathBuf, BufWriter<File>)>,
+    written: u64,
+    last_dir: Option<Direction>,
+    line_start: bool,
+    /// line timestamps of the text format, kept in sync with the RX display
+    pub timestamps: TimestampMode,
+    started: SystemTime,
+    prev_line: Option<SystemTime>,
+}
+
+impl Logger {
+    pub fn new(path: Option<PathBuf>, format: LogFormat, max_size: Option<u64>) -> Self {
+        Self {
+            path,
+            port: None,
+            format,
+            max_size,
+            file: None,
+            written: 0,
+            last_dir: None,
+            line_start: true,
+            timestamps: TimestampMode::Off,
+            started: SystemTime::now(),
+            prev_line: None,
+        }
+    }
+    /// Logger of another port with the same settings, writing to a file named after the
+    /// port, e.g. `session-ttyUSB1.log` next to `session.log`
+    pub fn for_port(&self, port: &str) -> Self {
+        let port = port.replace(['/', '\\', ':'], "-");
+        let path = self.path.as_ref().map(|p| {
+            let mut name = p.file_stem().unwrap_or_default().to_os_string();
+            name.push(format!("-{port}"));
+            if let Some(ext) = p.extension() {
+                name.push(".");
+                name.push(ext);
+            }
+            p.with_file_name(name)
+        });
+        let mut logger = Self::new(path, self.format, self.max_size);
+        logger.port = Some(port);
+        logger
+    }
+    pub fn is_active(&self) -> bool {
+        self.file.is_some()
+    }
+    /// Path of the file currently being written
+    pub fn path(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(p, _)| p.as_path())
+    }
+    /// Opens the log file in append mode
+    pub fn start(&mut self) -> io::Result<()> {
+        let path = self.path.clone().unwrap_or_else(|| {
+            let stamp = DateTime::now().file_stamp();
+            match &self.port {
+                Some(port) => PathBuf::from(format!("tuicom-{port}-{stamp}.log")),
+                None => PathBuf::from(format!("tuicom-{stamp}.log")),
+            }
+        });
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.written = file.metadata()?.len();
+        self.file = Some((path, BufWriter::new(file)));
+        self.last_dir = None;
+        self.line_start = true;
+        Ok(())
+    }
+    pub fn stop(&mut self) -> io::Result<()> {
+        if let Some((_, mut file)) = self.file.take() {
+            if self.format != LogFormat::Raw && !self.line_start {
+                file.write_all(b"\n")?;
+            }
+            file.flush()?;
+        }
+        self.line_start = true;
+        Ok(())
+    }
+    pub fn toggle(&mut self) -> io::Result<()> {
+        if self.is_active() {
+            self.stop()
+        } else {
+            self.start()
+        }
+    }
+    /// Records `bytes` going in `dir`, if logging is active
+    pub fn log(&mut self, dir: Direction, bytes: &[u8]) -> io::Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            return Ok(());
+        }
+        let mut out = Vec::with_capacity(bytes.len());
+        match self.format {
+            LogFormat::Raw if dir == Direction::Rx => out.extend_from_slice(bytes),
+            LogFormat::Text if dir == Direction::Rx => {
+                for &b in bytes {
+                    if self.line_start {
+                        let now = SystemTime::now();
+                        if let Some(stamp) =
+                            self.timestamps.stamp(now, self.started, self.prev_line)
+                        {
+                            out.extend_from_slice(stamp.as_bytes());
+                        }
+                        self.prev_line = Some(now);
+                        self.line_start = false;
+                    }
+                    if b == b'\n' {
+                        self.line_start = true;
+                    }
+                    match b {
+                        b'\t' | b'\r' | b'\n' => out.push(b),
+                        _ => push_escaped(&mut out, b),
+                    }
+                }
+            }
+            LogFormat::Raw | LogFormat::Text => (),
+            LogFormat::Tagged => {
+                if self.last_dir != Some(dir) && !self.line_start {
+                    out.push(b'\n');
+                    self.line_start = true;
+                }
+                self.last_dir = Some(dir);
+                for &b in bytes {
+                    if self.line_start {
+                        out.extend(format!("{} {} ", DateTime::now(), dir.tag()).bytes());
+                        self.line_start = false;
+                    }
+                    match b {
+                        b'\n' => {
+                            out.push(b'\n');
+                            self.line_start = true;
+                        }
+                        b'\r' => out.extend_from_slice(b"\\r"),
+                        b'\t' => out.push(b),
+                        _ => push_escaped(&mut out, b),
+                    }
+                }
+            }
+        }
+        self.write(&out)
+    }
+    /// Records an out of band event such as a BREAK, on a line of its own
+    pub fn event(&mut self, text: &str) -> io::Result<()> {
+        if self.file.is_none() {
+            return Ok(());
+        }
+        let mut out = String::new();
+        if !self.line_start {
+            out.push('\n');
+        }
+        match self.format {
+            LogFormat::Raw => return Ok(()),
+            LogFormat::Text => out.push_str(&format!("── {text} ──\n")),
+            LogFormat::Tagged => out.push_str(&format!("{} EV {text}\n", DateTime::now())),
+        }
+        self.line_start = true;
+        self.last_dir = None;
+        self.write(out.as_bytes())
+    }
+    /// Appends to the file, rotating it once it reaches `max_size`
+    fn write(&mut self, out: &[u8]) -> io::Result<()> {
+        let Some((_, file)) = &mut self.file else {
+            return Ok(());
+        };
+        file.write_all(out)?;
+        file.flush()?;
+        self.written += out.len() as u64;
+        if self.max_size.is_some_and(|max| self.written >= max) {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+    /// Moves the current file to the first free `<path>.N` and starts a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        let Some((path, _)) = &self.file else {
+            return Ok(());
+        };
+        let path = path.clone();
+        self.stop()?;
+        let mut n = 1;
+        let rotated = loop {
+            let mut name = path.clone().into_os_string();
+            name.push(format!(".{n}"));
+            let candidate = PathBuf::from(name);
+            if !candidate.exists() {
+                break candidate;
+            }
+            n += 1;
+        };
+        fs::rename(&path, rotated)?;
+        let file = File::create(&path)?;
+        self.written = 0;
+        self.last_dir = None;
+        self.file = Some((path, BufWriter::new(file)));
+        Ok(())
+    }
+}
+
+/// Pushes `b` as is if printable, escaped as `\xNN` otherwise.
+/// Bytes above 0x7F are kept so UTF-8 text survives.
+fn push_escaped(out: &mut Vec<u8>, b: u8) {
+    if b == b'\\' {
+        out.extend_from_slice(b"\\\\");
+    } else if b.is_ascii_control() {
+        out.extend(format!("\\x{b:02X}").bytes());
+    } else {
+        out.push(b);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::escape;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Named byte sequence sent with a function key or from the macro popup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    /// function key number, 1 to 12
+    pub key: Option<u8>,
+    /// text with C-style escapes, or hex bytes after a `hex:` prefix
+    pub data: String,
+}
+
+impl Macro {
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self.data.strip_prefix("hex:") {
+            Some(hex) => parse_hex(hex),
+            None => escape::unescape(&self.data),
+        }
+    }
+    pub fn key_name(&self) -> String {
+        self.key
+            .map_or_else(|| String::from("-"), |n| format!("F{n}"))
+    }
+}
+
+/// Macro list, kept in a file with one `<F1..F12 or -> <name> = <data>` line per macro
+#[derive(Debug, Clone, Default)]
+pub struct Macros {
+    pub list: Vec<Macro>,
+    path: Option<PathBuf>,
+}
+
+impl Macros {
+    /// Macros not backed by a file, edits are not saved
+    pub fn from_list(list: Vec<Macro>) -> Self {
+        Self { list, path: None }
+    }
+    /// Loads the macros in `path`, if it exists. Lines that can't be parsed are skipped
+    /// and the first error returned along the rest.
+    pub fn load(path: Option<PathBuf>) -> (Self, Option<String>) {
+        let mut macros = Self {
+            list: Vec::new(),
+            path,
+        };
+        let Some(text) = macros
+            .path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+        else {
+            return (macros, None);
+        };
+        let mut error = None;
+        for (i, line) in text.lines().enumerate() {
+            // the data may start or end with spaces
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match parse_line(line) {
+                Ok(m) => macros.list.push(m),
+                Err(e) => {
+                    error.get_or_insert(format!("macros line {}: {e}", i + 1));
+                }
+            }
+        }
+        (macros, error)
+    }
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut text =
+            String::from("# <F1..F12 or -> <name> = <text with \\escapes or hex:XX XX>\n");
+        for m in &self.list {
+            text.push_str(&format!("{} {} = {}\n", m.key_name(), m.name, m.data));
+        }
+        fs::write(path, text)
+    }
+    /// Adds or replaces (at `index`) a macro, taking its function key from any other macro
+    pub fn put(&mut self, index: Option<usize>, m: Macro) -> usize {
+        if m.key.is_some() {
+            for (i, other) in self.list.iter_mut().enumerate() {
+                if other.key == m.key && Some(i) != index {
+                    other.key = None;
+                }
+            }
+        }
+        match index {
+            Some(i) => {
+                self.list[i] = m;
+                i
+            }
+            None => {
+                self.list.push(m);
+                self.list.len() - 1
+            }
+        }
+    }
+}
+
+pub fn parse_key(s: &str) -> Result<Option<u8>, String> {
+    let s = s.trim();
+    if s.is_empty() || s == "-" {
+        return Ok(None);
+    }
+    match s.strip_prefix(['F', 'f']).and_then(|n| n.parse().ok()) {
+        Some(n @ 1..=12) => Ok(Some(n)),
+        _ => Err(format!("invalid key `{s}`, expected F1 to F12 or -")),
+    }
+}
+
+fn parse_line(line: &str) -> Result<Macro, String> {
+    let (head, data) = line
+        .split_once(" = ")
+        .ok_or_else(|| String::from("expected `<key> <name> = <data>`"))?;
+    let (key, name) = head.trim().split_once(' ').unwrap_or((head.trim(), ""));
+    let m = Macro {
+        name: name.trim().to_string(),
+        key: parse_key(key)?,
+        data: data.to_string(),
+    };
+    if m.name.is_empty() {
+        return Err(String::from("missing name"));
+    }
+    m.bytes()?;
+    Ok(m)
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    let digits = s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    if digits.len() % 2 != 0 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid hex bytes `{}`", s.trim()));
+    }
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
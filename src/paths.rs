@@ -0,0 +1,28 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Directory for persistent state such as the send history,
+/// `$XDG_STATE_HOME/tuicom` or `~/.local/state/tuicom`
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Directory for user configuration such as macros,
+/// `$XDG_CONFIG_HOME/tuicom` or `~/.config/tuicom`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+fn xdg_dir(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    let base = env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| home_dir().map(|h| h.join(home_fallback)))?;
+    Some(base.join("tuicom"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
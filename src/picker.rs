@@ -0,0 +1,135 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serialport::{SerialPortInfo, SerialPortType};
+
+/// Outcome of a key press in the port picker
+#[derive(Debug, Clone)]
+pub enum Pick {
+    None,
+    Cancel,
+    Selected(String),
+}
+
+/// Interactive selection of one of the available serial ports
+#[derive(Debug, Clone)]
+pub struct PortPicker {
+    ports: Vec<SerialPortInfo>,
+    pub filter: String,
+    pub selected: usize,
+    pub error: Option<String>,
+    /// whether to clear the consoles when switching to the picked port, `None` when there
+    /// is no session yet
+    pub clear_history: Option<bool>,
+}
+
+impl Default for PortPicker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl PortPicker {
+    pub fn new() -> Self {
+        let mut picker = Self {
+            ports: Vec::new(),
+            filter: String::new(),
+            selected: 0,
+            error: None,
+            clear_history: None,
+        };
+        picker.refresh();
+        picker
+    }
+    /// Rescans the system for serial ports
+    pub fn refresh(&mut self) {
+        match serialport::available_ports() {
+            Ok(ports) => {
+                self.ports = ports;
+                self.error = None;
+            }
+            Err(e) => {
+                self.ports.clear();
+                self.error = Some(e.to_string());
+            }
+        }
+        self.selected = 0;
+    }
+    /// Ports matching the current filter
+    pub fn filtered(&self) -> Vec<&SerialPortInfo> {
+        let filter = self.filter.to_lowercase();
+        self.ports
+            .iter()
+            .filter(|p| {
+                filter.is_empty()
+                    || p.port_name.to_lowercase().contains(&filter)
+                    || describe(p).to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+    pub fn handle_key(&mut self, key: KeyEvent) -> Pick {
+        use KeyCode as K;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let count = self.filtered().len();
+        match key.code {
+            K::Esc => return Pick::Cancel,
+            K::Enter => {
+                if let Some(p) = self.filtered().get(self.selected) {
+                    return Pick::Selected(p.port_name.clone());
+                }
+                // a port that isn't listed, e.g. a pty, given by its path
+                if !self.filter.is_empty() {
+                    return Pick::Selected(self.filter.clone());
+                }
+            }
+            K::Char('r') if ctrl => self.refresh(),
+            K::Char('x') if ctrl => {
+                if let Some(clear) = &mut self.clear_history {
+                    *clear = !*clear;
+                }
+            }
+            K::Char('n') if ctrl => self.select_next(count),
+            K::Char('p') if ctrl => self.select_prev(count),
+            K::F(5) => self.refresh(),
+            K::Down | K::Tab => self.select_next(count),
+            K::Up | K::BackTab => self.select_prev(count),
+            K::Char(c) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            K::Backspace => {
+                self.filter.pop();
+                self.selected = 0;
+            }
+            _ => (),
+        }
+        Pick::None
+    }
+    fn select_next(&mut self, count: usize) {
+        if count > 0 {
+            self.selected = (self.selected + 1) % count;
+        }
+    }
+    fn select_prev(&mut self, count: usize) {
+        if count > 0 {
+            self.selected = (self.selected + count - 1) % count;
+        }
+    }
+}
+
+/// Human readable description of the port type
+pub fn describe(info: &SerialPortInfo) -> String {
+    match &info.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let mut s = format!("USB {:04x}:{:04x}", usb.vid, usb.pid);
+            for field in [&usb.manufacturer, &usb.product].into_iter().flatten() {
+                s.push(' ');
+                s.push_str(field);
+            }
+            if let Some(sn) = &usb.serial_number {
+                s.push_str(&format!(" (S/N {sn})"));
+            }
+            s
+        }
+        SerialPortType::PciPort => String::from("PCI"),
+        SerialPortType::BluetoothPort => String::from("Bluetooth"),
+        SerialPortType::Unknown => String::from("Unknown"),
+    }
+}
@@ -1,172 +1,942 @@
-use serialport::SerialPort;
-use std::io;
+use crate::clock::TimestampMode;
+use crate::decode::{ControlStyle, Decoder, Encoding, HexEscape};
+use crate::line_ending::{LineEnding, RxNewline};
+use crate::vt::Vt;
+use itertools::Itertools;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::SystemTime;
+use unicode_width::UnicodeWidthChar;
 
 /// TX console
 pub struct Tx {
     pub display: Display,
-    pub lf_crlf: bool,
+    pub line_ending: LineEnding,
+    /// bytes written by the last `send`
+    sent: Vec<u8>,
+}
+impl Default for Tx {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl Tx {
     pub fn new() -> Self {
         Self {
             display: Display::new(),
-            lf_crlf: false,
+            line_ending: LineEnding::Lf,
+            sent: Vec::with_capacity(2),
         }
     }
-    pub fn with_cursor<'a>(&'a mut self, cursor: char) -> WithCursor<'a> {
-        WithCursor::new(&mut self.display.show, cursor)
-    }
     pub fn is_hex(&self) -> bool {
-        match &self.display.display_mode {
-            DisplayMode::Ascii => false,
-            DisplayMode::Hex(_) => true,
-        }
+        self.display.is_hex()
     }
     pub fn is_ascii(&self) -> bool {
-        match &self.display.display_mode {
-            DisplayMode::Ascii => true,
-            DisplayMode::Hex(_) => false,
-        }
+        matches!(
+            self.display.display_mode,
+            DisplayMode::Ascii | DisplayMode::Mixed
+        )
     }
-    pub fn send(&mut self, ch: u8, port: &mut dyn SerialPort) -> Result<(), io::Error> {
-        let Some(c) = self.display.push_char(ch) else {return Ok(())};
+    /// Sends a typed character, returning the bytes actually written to the port. Enter
+    /// sends the line ending in the text views, bytes typed in hex go out as they are.
+    pub fn send(&mut self, ch: u8, port: &mut dyn Write) -> Result<&[u8], io::Error> {
+        self.sent.clear();
+        let Some(c) = self.display.push_char(ch) else {
+            return Ok(&self.sent);
+        };
 
-        let res = if self.lf_crlf && c == b'\n' {
-            port.write_all(b"\r\n")
+        if c == b'\n' && !self.is_hex() {
+            self.sent.extend_from_slice(self.line_ending.bytes());
         } else {
-            port.write_all(&[c])
-        };
-        if let Err(_) = &res {
+            self.sent.push(c);
+        }
+        if self.sent.is_empty() {
+            return Ok(&self.sent);
+        }
+        if let Err(e) = port.write_all(&self.sent) {
             self.display.pop();
+            return Err(e);
         }
-        res
+        Ok(&self.sent)
     }
 }
 
 /// RX console
 pub struct Rx {
     pub display: Display,
-    recv_buf: Vec<u8>,
+    /// terminal emulator fed alongside the display while ANSI mode is on
+    pub vt: Option<Vt>,
 }
 
+impl Default for Rx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Rx {
     pub fn new() -> Self {
         Self {
             display: Display::new(),
-            recv_buf: Vec::new(),
+            vt: None,
         }
     }
-    pub fn is_hex(&self) -> bool {
-        match &self.display.display_mode {
-            DisplayMode::Ascii => false,
-            DisplayMode::Hex(_) => true,
+    pub fn is_ansi(&self) -> bool {
+        self.vt.is_some()
+    }
+    /// Toggles ANSI terminal emulation, replaying what was received so far
+    pub fn switch_ansi(&mut self) {
+        self.vt = match self.vt {
+            Some(_) => None,
+            None => Some(self.replay(24, 80)),
+        };
+    }
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.display.set_encoding(encoding);
+        if let Some(vt) = &self.vt {
+            let (rows, cols) = vt.size();
+            self.vt = Some(self.replay(rows, cols));
+        }
+    }
+    pub fn clear(&mut self) {
+        self.display.clear();
+        if let Some(vt) = &self.vt {
+            let (rows, cols) = vt.size();
+            self.vt = Some(Vt::new(rows, cols, self.display.encoding()));
         }
     }
+    /// Answers the emulator owes the remote end, e.g. a cursor position report
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        self.vt.as_mut().map(Vt::take_replies).unwrap_or_default()
+    }
+    /// Builds an emulator from the received bytes, dropping queries answered long ago
+    fn replay(&self, rows: usize, cols: usize) -> Vt {
+        let mut vt = Vt::new(rows, cols, self.display.encoding());
+        let (front, back) = self.display.buffer.as_slices();
+        vt.feed(front);
+        vt.feed(back);
+        vt.take_replies();
+        vt
+    }
+    pub fn is_hex(&self) -> bool {
+        self.display.is_hex()
+    }
     pub fn is_ascii(&self) -> bool {
-        match &self.display.display_mode {
-            DisplayMode::Ascii => true,
-            DisplayMode::Hex(_) => false,
+        matches!(
+            self.display.display_mode,
+            DisplayMode::Ascii | DisplayMode::Mixed
+        )
+    }
+    /// Shows bytes read from the port
+    pub fn receive(&mut self, data: &[u8]) {
+        self.display.push_bytes(data);
+        if let Some(vt) = &mut self.vt {
+            vt.feed(data);
         }
     }
-    pub fn with_cursor<'a>(&'a mut self, cursor: char) -> WithCursor<'a> {
-        WithCursor::new(&mut self.display.show, cursor)
+}
+
+/// Scroll position of a console, in wrapped rows
+#[derive(Debug, Clone, Default)]
+pub struct Scroll {
+    /// first visible row, `None` while following new data
+    pub top: Option<usize>,
+    /// total rows of the last rendered frame
+    pub rows: usize,
+    /// visible rows of the last rendered frame
+    pub height: usize,
+}
+
+impl Scroll {
+    pub fn is_following(&self) -> bool {
+        self.top.is_none()
+    }
+    fn max_top(&self) -> usize {
+        self.rows.saturating_sub(self.height)
     }
-    pub fn recv(&mut self, port: &mut dyn SerialPort) -> Result<(), io::Error> {
-        let bytes = port.bytes_to_read()? as usize;
-        self.recv_buf.resize(bytes as usize, 0);
-        port.read_exact(&mut self.recv_buf[..])?;
-        for &b in &self.recv_buf {
-            self.display.push_byte(b);
+    /// First visible row
+    pub fn offset(&self) -> usize {
+        self.top.map_or(self.max_top(), |t| t.min(self.max_top()))
+    }
+    pub fn up(&mut self, n: usize) {
+        self.top = Some(self.offset().saturating_sub(n));
+    }
+    /// Scrolls down, following new data again once the bottom is reached
+    pub fn down(&mut self, n: usize) {
+        let top = self.offset() + n;
+        self.top = if top >= self.max_top() {
+            None
+        } else {
+            Some(top)
+        };
+    }
+    pub fn half_page(&self) -> usize {
+        (self.height / 2).max(1)
+    }
+    pub fn to_top(&mut self) {
+        self.top = Some(0);
+    }
+    pub fn to_bottom(&mut self) {
+        self.top = None;
+    }
+    /// Positions the view so `row` is roughly centered
+    pub fn show_row(&mut self, row: usize) {
+        self.top = Some(row.saturating_sub(self.height / 2).min(self.max_top()));
+    }
+}
+
+/// Incremental search state of a console
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub query: String,
+    /// index of the focused match, `None` to pick the first one in view
+    pub current: Option<usize>,
+    /// number of matches found in the last rendered frame
+    pub count: usize,
+    /// scroll to the focused match on the next render
+    pub jump: bool,
+}
+
+impl Search {
+    pub fn next(&mut self) {
+        if self.count > 0 {
+            self.current = Some(self.current.map_or(0, |c| (c + 1) % self.count));
+            self.jump = true;
         }
+    }
+    pub fn prev(&mut self) {
+        if self.count > 0 {
+            self.current = Some(
+                self.current
+                    .map_or(self.count - 1, |c| (c + self.count - 1) % self.count),
+            );
+            self.jump = true;
+        }
+    }
+}
+
+/// Rows of a console filled a character at a time, the way lines are drawn and laid out: a
+/// character too wide for what is left of a row starts the next one
+#[derive(Debug, Clone, Copy)]
+pub struct LineWrap {
+    width: usize,
+    used: usize,
+    rows: usize,
+}
 
-        Ok(())
+impl LineWrap {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            used: 0,
+            rows: 1,
+        }
+    }
+    /// Adds `c`, returning whether it starts a new row
+    pub fn push(&mut self, c: char) -> bool {
+        let w = c.width().unwrap_or(0);
+        let new_row = self.used + w > self.width;
+        if new_row {
+            self.rows += 1;
+            self.used = 0;
+        }
+        self.used += w;
+        new_row
+    }
+    pub fn push_str(&mut self, s: &str) {
+        for c in s.chars() {
+            self.push(c);
+        }
     }
+    /// Rows taken so far, at least one
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+/// Longest line kept, in bytes of the stream. Longer lines, e.g. binary data without line
+/// breaks, are split so that every line stays cheap to lay out and search.
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Line of a console as rendered for the text, mixed and hex views
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// stream offset of the first byte of the line
+    start: u64,
+    /// bytes of the stream on the line, hidden ones included
+    len: usize,
+    /// arrival time of the first shown byte, `None` while there is none
+    time: Option<SystemTime>,
+    pub text: String,
+    /// ranges of `text` holding escaped bytes in the mixed view, in order
+    pub escapes: Vec<Range<usize>>,
+}
+
+impl Line {
+    fn new(start: u64) -> Self {
+        Self {
+            start,
+            len: 0,
+            time: None,
+            text: String::new(),
+            escapes: Vec::new(),
+        }
+    }
+}
+
+/// Wrapped rows of the lines at the width they were last drawn at, extended as lines come
+/// and trimmed as they go so that drawing costs the same however long the history is
+#[derive(Debug, Clone, Default)]
+struct Layout {
+    width: usize,
+    timestamps: TimestampMode,
+    /// first row of each laid out line, counted from an arbitrary origin
+    starts: VecDeque<usize>,
+}
+
+/// Matches of the text search, found as lines arrive
+#[derive(Debug, Clone, Default)]
+struct Found {
+    pattern: String,
+    /// line number, counting dropped lines, and text range of every match, in order
+    matches: VecDeque<(u64, Range<usize>)>,
+    /// number of the first line left to search. The last line is searched again as it grows.
+    searched: u64,
+}
+
+/// Query of the hexdump search
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BytePattern {
+    pub bytes: Vec<u8>,
+    /// typed as hex digits, so matched exactly rather than with smart case
+    pub hex: bool,
+}
+
+/// Matches of the hexdump search, found as bytes arrive
+#[derive(Debug, Clone, Default)]
+struct FoundBytes {
+    pattern: BytePattern,
+    /// stream offsets of every match, in order
+    matches: VecDeque<Range<u64>>,
+    /// stream offset searched up to
+    searched: u64,
 }
 
 pub struct Display {
-    buffer: Vec<u8>,
-    show: String,
+    /// bytes of the kept lines
+    buffer: VecDeque<u8>,
+    /// stream offset of the first byte of `buffer`
+    base: u64,
+    /// stream offset and arrival time of each pushed chunk
+    chunk_times: VecDeque<(u64, SystemTime)>,
+    /// out of band events (e.g. a BREAK) and the stream offset they happened at
+    events: VecDeque<(u64, SystemTime, String)>,
+    /// rendered lines, only the last one still changes. In the hexdump view they only
+    /// keep track of where lines start, to know which bytes to drop.
+    lines: VecDeque<Line>,
+    /// lines dropped from the front so far
+    dropped: u64,
+    /// most lines kept
+    pub scrollback: usize,
+    /// most bytes kept, whatever the number of lines
+    byte_limit: usize,
+    layout: Layout,
+    found: Found,
+    found_bytes: FoundBytes,
     display_mode: DisplayMode,
+    newline: RxNewline,
+    decoder: Decoder,
+    after_cr: bool,
+    started: SystemTime,
+    pub timestamps: TimestampMode,
+    pub hex_escape: HexEscape,
+    pub dump_width: DumpWidth,
+    pub scroll: Scroll,
+    pub search: Option<Search>,
 }
 
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Display {
+    pub const DEFAULT_SCROLLBACK: usize = 100_000;
+    /// Most bytes kept, so that long lines, e.g. binary data, don't fill the memory
+    pub const BYTE_LIMIT: usize = 16 << 20;
+
     pub fn new() -> Self {
         Self {
-            buffer: Vec::new(),
-            show: String::new(),
+            buffer: VecDeque::new(),
+            base: 0,
+            chunk_times: VecDeque::new(),
+            events: VecDeque::new(),
+            lines: VecDeque::from([Line::new(0)]),
+            dropped: 0,
+            scrollback: Self::DEFAULT_SCROLLBACK,
+            byte_limit: Self::BYTE_LIMIT,
+            layout: Layout::default(),
+            found: Found::default(),
+            found_bytes: FoundBytes::default(),
             display_mode: DisplayMode::Ascii,
+            newline: RxNewline::Lf,
+            decoder: Decoder::new(Encoding::Utf8, ControlStyle::Picture),
+            after_cr: false,
+            started: SystemTime::now(),
+            timestamps: TimestampMode::Off,
+            hex_escape: HexEscape::Angle,
+            dump_width: DumpWidth::Auto,
+            scroll: Scroll::default(),
+            search: None,
         }
     }
+    /// Rendered lines, never empty
+    pub fn lines(&self) -> &VecDeque<Line> {
+        &self.lines
+    }
     pub fn clear(&mut self) {
         self.buffer.clear();
-        self.show.clear();
+        self.base = 0;
+        self.chunk_times.clear();
+        self.events.clear();
+        self.scroll = Scroll::default();
+        self.search = None;
         self.display_mode.clear();
+        self.clear_render();
     }
-    pub fn switch_hex(&mut self) {
-        self.display_mode = match self.display_mode {
-            DisplayMode::Ascii => DisplayMode::Hex(ByteBuffer { buf: None }),
-            DisplayMode::Hex(_) => DisplayMode::Ascii,
-        };
-        self.show.clear();
-
-        if let DisplayMode::Ascii = self.display_mode {
-            for &b in &self.buffer {
-                push_ascii(&mut self.show, b);
-            }
-        } else {
-            for &b in &self.buffer {
-                push_hex(&mut self.show, b);
-            }
+    /// Whether typed characters are hex digits, in the hex and hexdump views
+    pub fn is_hex(&self) -> bool {
+        matches!(
+            self.display_mode,
+            DisplayMode::Hex(_) | DisplayMode::Dump(_)
+        )
+    }
+    pub fn is_dump(&self) -> bool {
+        matches!(self.display_mode, DisplayMode::Dump(_))
+    }
+    /// Name of the view shown in the console title, `None` for plain text
+    pub fn view_name(&self) -> Option<&'static str> {
+        match self.display_mode {
+            DisplayMode::Ascii => None,
+            DisplayMode::Mixed => Some("Mixed"),
+            DisplayMode::Hex(_) => Some("Hex"),
+            DisplayMode::Dump(_) => Some("Hexdump"),
         }
     }
-    pub fn pop(&mut self) -> Option<u8> {
+    /// Received (or sent) bytes still kept
+    pub fn bytes(&self) -> &VecDeque<u8> {
+        &self.buffer
+    }
+    /// Stream offset of the first kept byte
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+    /// Stream offset after the last byte
+    pub fn end(&self) -> u64 {
+        self.base + self.buffer.len() as u64
+    }
+    /// Stream offset of the first byte of the `i`th line
+    pub fn line_start(&self, i: usize) -> u64 {
+        self.lines[i].start
+    }
+    /// High nibble typed in a hex view, waiting for the low one
+    pub fn pending_nibble(&self) -> Option<u8> {
         match self.display_mode {
-            DisplayMode::Hex(_) => {
-                for _ in 0..3 {
-                    self.show.pop();
-                }
-            }
-            DisplayMode::Ascii => {
-                self.show.pop();
-            }
+            DisplayMode::Hex(b) | DisplayMode::Dump(b) => b.buf,
+            DisplayMode::Ascii | DisplayMode::Mixed => None,
+        }
+    }
+    /// Search query adapted to the display mode. In hex mode `4865` matches `48 65`.
+    pub fn search_pattern(&self) -> Option<String> {
+        let query = &self.search.as_ref()?.query;
+        if query.is_empty() {
+            return None;
         }
-        self.buffer.pop()
+        if matches!(self.display_mode, DisplayMode::Hex(_))
+            && query.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            let digits = query.to_ascii_uppercase().into_bytes();
+            Some(digits.chunks(2).map(String::from_utf8_lossy).join(" "))
+        } else {
+            Some(query.clone())
+        }
+    }
+    /// Search query as bytes for the hexdump view. An even number of hex digits is taken as
+    /// hex, anything else as text.
+    pub fn search_bytes(&self) -> Option<BytePattern> {
+        let query = &self.search.as_ref()?.query;
+        if query.is_empty() {
+            return None;
+        }
+        if query.len().is_multiple_of(2) && query.chars().all(|c| c.is_ascii_hexdigit()) {
+            let bytes = (0..query.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&query[i..i + 2], 16).unwrap())
+                .collect();
+            Some(BytePattern { bytes, hex: true })
+        } else {
+            Some(BytePattern {
+                bytes: query.clone().into_bytes(),
+                hex: false,
+            })
+        }
+    }
+    /// Shows the console in `view`
+    pub fn set_view(&mut self, view: View) {
+        let empty = ByteBuffer { buf: None };
+        self.display_mode = match view {
+            View::Text => DisplayMode::Ascii,
+            View::Mixed => DisplayMode::Mixed,
+            View::Hex => DisplayMode::Hex(empty),
+            View::Hexdump => DisplayMode::Dump(empty),
+        };
+        self.scroll = Scroll::default();
+        self.rerender();
+    }
+    /// Cycles between the text, mixed, hex and hexdump views
+    pub fn switch_view(&mut self) {
+        let empty = ByteBuffer { buf: None };
+        self.display_mode = match self.display_mode {
+            DisplayMode::Ascii => DisplayMode::Mixed,
+            DisplayMode::Mixed => DisplayMode::Hex(empty),
+            DisplayMode::Hex(_) => DisplayMode::Dump(empty),
+            DisplayMode::Dump(_) => DisplayMode::Ascii,
+        };
+        self.scroll = Scroll::default();
+        self.rerender();
+    }
+    pub fn newline(&self) -> RxNewline {
+        self.newline
+    }
+    pub fn set_newline(&mut self, newline: RxNewline) {
+        self.newline = newline;
+        self.rerender();
+    }
+    pub fn encoding(&self) -> Encoding {
+        self.decoder.encoding
+    }
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.decoder.encoding = encoding;
+        self.rerender();
+    }
+    pub fn control_style(&self) -> ControlStyle {
+        self.decoder.control
+    }
+    pub fn set_control_style(&mut self, control: ControlStyle) {
+        self.decoder.control = control;
+        self.rerender();
+    }
+    pub fn set_hex_escape(&mut self, hex_escape: HexEscape) {
+        self.hex_escape = hex_escape;
+        self.rerender();
+    }
+    pub fn pop(&mut self) -> Option<u8> {
+        let b = self.buffer.pop_back();
+        let end = self.end();
+        self.chunk_times.retain(|&(offset, _)| offset < end);
+        self.rerender();
+        b
     }
     /// pushes an ASCII digit to buffer and display, accounting for HEX mode
     pub fn push_char(&mut self, ch: u8) -> Option<u8> {
         let mut out = None;
         match &mut self.display_mode {
-            DisplayMode::Ascii => {
-                self.buffer.push(ch);
-                push_ascii(&mut self.show, ch);
+            DisplayMode::Ascii | DisplayMode::Mixed => {
+                self.push_bytes(&[ch]);
                 out = Some(ch);
             }
-            DisplayMode::Hex(byte_buf) => {
-                // only display if is valid number
-                if let Some(c) = (ch as char).to_digit(16) {
-                    let mut complete = false;
-                    if let Some(b) = byte_buf.push(c as u8) {
-                        self.buffer.push(b);
-                        out = Some(b);
-                        complete = true;
-                    }
-                    self.show.push(ch.to_ascii_uppercase().into());
-                    if complete {
-                        self.show.push(' ');
-                    }
+            // the high nibble waits in the view state, and is drawn after the bytes
+            DisplayMode::Hex(byte_buf) | DisplayMode::Dump(byte_buf) => {
+                if let Some(b) = (ch as char)
+                    .to_digit(16)
+                    .and_then(|c| byte_buf.push(c as u8))
+                {
+                    self.push_bytes(&[b]);
+                    out = Some(b);
                 }
             }
         }
         out
     }
-    pub fn push_byte(&mut self, byte: u8) {
-        self.buffer.push(byte);
-        self.show_push(byte);
+    /// Appends bytes that arrived together
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        self.push_bytes_at(bytes, SystemTime::now());
     }
-    fn show_push(&mut self, byte: u8) {
-        match &mut self.display_mode {
-            DisplayMode::Ascii => push_ascii(&mut self.show, byte),
-            DisplayMode::Hex(_) => push_hex(&mut self.show, byte),
+    /// Appends bytes stamped with the time they arrived at, e.g. a line put together
+    /// before being pushed
+    pub fn push_bytes_at(&mut self, bytes: &[u8], at: SystemTime) {
+        if bytes.is_empty() {
+            return;
+        }
+        let start = self.end();
+        self.chunk_times.push_back((start, at));
+        self.buffer.extend(bytes);
+        for (i, &b) in bytes.iter().enumerate() {
+            self.render_byte(b, start + i as u64, at);
+        }
+        self.trim();
+    }
+    /// Records an event, shown on a line of its own between the surrounding data
+    pub fn push_event(&mut self, text: impl Into<String>) {
+        let now = SystemTime::now();
+        let text = text.into();
+        self.render_event(&text, now);
+        self.events.push_back((self.end(), now, text));
+        self.trim();
+    }
+    /// Timestamp prefix of the `line`th line, if timestamps are on and the line was received.
+    /// The hexdump view has no lines.
+    pub fn line_stamp(&self, line: usize) -> Option<String> {
+        if self.is_dump() {
+            return None;
+        }
+        let at = self.lines.get(line)?.time?;
+        let prev = line.checked_sub(1).and_then(|l| self.lines[l].time);
+        self.timestamps.stamp(at, self.started, prev)
+    }
+    /// Lays the lines out in rows of `width` columns, returning how many rows they take
+    pub fn layout(&mut self, width: usize) -> usize {
+        if self.layout.width != width || self.layout.timestamps != self.timestamps {
+            self.layout = Layout {
+                width,
+                timestamps: self.timestamps,
+                starts: VecDeque::new(),
+            };
+        }
+        if self.layout.starts.is_empty() {
+            self.layout.starts.push_back(0);
+        }
+        while self.layout.starts.len() < self.lines.len() {
+            let prev = self.layout.starts.len() - 1;
+            let start = self.layout.starts[prev] + self.line_rows(prev, width);
+            self.layout.starts.push_back(start);
+        }
+        let last = self.lines.len() - 1;
+        self.line_row(last) + self.line_rows(last, width)
+    }
+    /// Rows taken by the `i`th line at `width`, its timestamp included
+    fn line_rows(&self, i: usize, width: usize) -> usize {
+        self.line_wrap(i, None, width).rows()
+    }
+    /// Rows of `width` filled with the timestamp of the `i`th line and its text, up to
+    /// byte `end`
+    pub fn line_wrap(&self, i: usize, end: Option<usize>, width: usize) -> LineWrap {
+        let mut wrap = LineWrap::new(width);
+        if let Some(stamp) = self.line_stamp(i) {
+            wrap.push_str(&stamp);
+        }
+        let text = &self.lines[i].text;
+        wrap.push_str(&text[..end.unwrap_or(text.len())]);
+        wrap
+    }
+    /// First row of the `i`th line, as of the last `layout`
+    pub fn line_row(&self, i: usize) -> usize {
+        self.layout.starts[i] - self.layout.starts[0]
+    }
+    /// Line shown on `row`, as of the last `layout`
+    pub fn line_at_row(&self, row: usize) -> usize {
+        let row = row + self.layout.starts[0];
+        self.layout
+            .starts
+            .partition_point(|&s| s <= row)
+            .saturating_sub(1)
+    }
+    /// Finds the matches of `pattern` in the lines not searched yet, returning how many
+    /// there are in all
+    pub fn find(&mut self, pattern: Option<&str>) -> usize {
+        let Some(pattern) = pattern else {
+            self.found = Found::default();
+            return 0;
+        };
+        if self.found.pattern != pattern {
+            self.found = Found {
+                pattern: pattern.to_string(),
+                matches: VecDeque::new(),
+                searched: self.dropped,
+            };
+        }
+        let found = &mut self.found;
+        while found.matches.back().is_some_and(|m| m.0 >= found.searched) {
+            found.matches.pop_back();
+        }
+        let first = found.searched.max(self.dropped);
+        for (i, line) in self
+            .lines
+            .iter()
+            .enumerate()
+            .skip((first - self.dropped) as usize)
+        {
+            let n = self.dropped + i as u64;
+            found.matches.extend(
+                find_matches(&line.text, &found.pattern)
+                    .into_iter()
+                    .map(|m| (n, m)),
+            );
+        }
+        found.searched = self.dropped + self.lines.len() as u64 - 1;
+        found.matches.len()
+    }
+    /// Line and text range of the `n`th match of the last `find`
+    pub fn nth_match(&self, n: usize) -> (usize, Range<usize>) {
+        let (line, range) = &self.found.matches[n];
+        ((line - self.dropped) as usize, range.clone())
+    }
+    /// Index of the first match on line `line` or after it
+    pub fn first_match_from(&self, line: usize) -> usize {
+        let line = self.dropped + line as u64;
+        self.found.matches.partition_point(|m| m.0 < line)
+    }
+    /// Row of the `n`th match of the last `find`, as of the last `layout`
+    pub fn match_row(&self, n: usize) -> usize {
+        let (line, range) = self.nth_match(n);
+        let mut wrap = self.line_wrap(line, Some(range.start), self.layout.width);
+        // the first character of the match may not fit the row
+        let text = &self.lines[line].text;
+        if let Some(c) = text[range.start..].chars().next() {
+            wrap.push(c);
+        }
+        self.line_row(line) + wrap.rows() - 1
+    }
+    /// Lays the bytes out in hexdump rows of `per_row` bytes, returning how many rows they
+    /// take with a slot for the cursor after them. Rows start at multiples of `per_row`, the
+    /// first one from `dump_first_row`.
+    pub fn dump_layout(&mut self, per_row: usize) -> usize {
+        if self.layout.width != per_row {
+            self.layout = Layout {
+                width: per_row,
+                ..Layout::default()
+            };
+        }
+        let per_row = per_row as u64;
+        ((self.end() + 1).div_ceil(per_row) - self.dump_first_row()) as usize
+    }
+    /// Row of the stream holding the first kept byte, as of the last `dump_layout`
+    pub fn dump_first_row(&self) -> u64 {
+        self.base / self.layout.width.max(1) as u64
+    }
+    /// Finds the matches of `pattern` in the bytes not searched yet, returning the stream
+    /// offsets of all of them
+    pub fn find_bytes(&mut self, pattern: Option<&BytePattern>) -> &VecDeque<Range<u64>> {
+        let found = &mut self.found_bytes;
+        let Some(pattern) = pattern else {
+            *found = FoundBytes::default();
+            return &found.matches;
+        };
+        if &found.pattern != pattern {
+            *found = FoundBytes {
+                pattern: pattern.clone(),
+                matches: VecDeque::new(),
+                searched: self.base,
+            };
+        }
+        // a match may straddle what was searched and what came since
+        let from = found
+            .searched
+            .saturating_sub(pattern.bytes.len() as u64 - 1)
+            .max(found.matches.back().map_or(0, |m| m.end))
+            .max(self.base);
+        let end = self.base + self.buffer.len() as u64;
+        let new = self
+            .buffer
+            .range((from - self.base) as usize..)
+            .copied()
+            .collect::<Vec<_>>();
+        found.matches.extend(
+            find_byte_matches(&new, pattern)
+                .into_iter()
+                .map(|m| from + m.start as u64..from + m.end as u64),
+        );
+        found.searched = end;
+        &found.matches
+    }
+    /// Drops the oldest lines beyond the scrollback limit or the byte limit, and their bytes
+    fn trim(&mut self) {
+        let mut excess = self.lines.len().saturating_sub(self.scrollback.max(1));
+        let end = self.end();
+        while excess + 1 < self.lines.len()
+            && end - self.lines[excess].start > self.byte_limit as u64
+        {
+            excess += 1;
+        }
+        if excess == 0 {
+            return;
+        }
+        let old_base = self.base;
+        let base = self.lines[excess].start;
+        // keep the view still while scrolled back
+        let rows = if self.is_dump() {
+            let per_row = self.layout.width.max(1) as u64;
+            (base / per_row - old_base / per_row) as usize
+        } else if self.layout.starts.len() > excess {
+            let rows = self.layout.starts[excess] - self.layout.starts[0];
+            self.layout.starts.drain(..excess);
+            rows
+        } else {
+            self.layout.starts.clear();
+            0
+        };
+        if let Some(top) = &mut self.scroll.top {
+            *top = top.saturating_sub(rows);
+        }
+        self.lines.drain(..excess);
+        self.dropped += excess as u64;
+        self.buffer.drain(..(base - old_base) as usize);
+        self.base = base;
+        // the chunk holding the first kept byte gives its time
+        while self
+            .chunk_times
+            .get(1)
+            .is_some_and(|&(offset, _)| offset <= base)
+        {
+            self.chunk_times.pop_front();
+        }
+        while self
+            .events
+            .front()
+            .is_some_and(|&(offset, ..)| offset < base)
+        {
+            self.events.pop_front();
+        }
+        let mut gone = 0;
+        while self
+            .found
+            .matches
+            .front()
+            .is_some_and(|m| m.0 < self.dropped)
+        {
+            self.found.matches.pop_front();
+            gone += 1;
+        }
+        while self
+            .found_bytes
+            .matches
+            .front()
+            .is_some_and(|m| m.start < base)
+        {
+            self.found_bytes.matches.pop_front();
+            gone += 1;
+        }
+        if let Some(current) = self.search.as_mut().and_then(|s| s.current.as_mut()) {
+            *current = current.saturating_sub(gone);
+        }
+    }
+    fn clear_render(&mut self) {
+        self.lines = VecDeque::from([Line::new(self.base)]);
+        self.layout = Layout::default();
+        self.found = Found::default();
+        self.found_bytes = FoundBytes::default();
+        self.after_cr = false;
+        self.decoder.reset();
+    }
+    /// Renders the whole buffer again, after a display setting changed
+    fn rerender(&mut self) {
+        self.clear_render();
+        let buffer = std::mem::take(&mut self.buffer);
+        let chunk_times = std::mem::take(&mut self.chunk_times);
+        let events = std::mem::take(&mut self.events);
+        let mut times = chunk_times.iter().peekable();
+        let mut pending_events = events.iter().peekable();
+        let mut at = self.started;
+        for (i, &b) in buffer.iter().enumerate() {
+            let offset = self.base + i as u64;
+            while let Some((_, t, text)) = pending_events.next_if(|(o, ..)| *o <= offset) {
+                self.render_event(text, *t);
+            }
+            while let Some(&(_, t)) = times.next_if(|&&(o, _)| o <= offset) {
+                at = t;
+            }
+            self.render_byte(b, offset, at);
+        }
+        for (_, t, text) in pending_events {
+            self.render_event(text, *t);
+        }
+        self.buffer = buffer;
+        self.chunk_times = chunk_times;
+        self.events = events;
+        // a different newline setting may make more lines of the same bytes
+        self.trim();
+    }
+    /// Starts a new line at stream offset `start`
+    fn break_line(&mut self, start: u64) {
+        self.lines.push_back(Line::new(start));
+    }
+    fn render_event(&mut self, text: &str, at: SystemTime) {
+        let line = self.lines.back_mut().unwrap();
+        self.decoder.flush(&mut line.text);
+        let start = line.start + line.len as u64;
+        if !line.text.is_empty() {
+            self.break_line(start);
+        }
+        let line = self.lines.back_mut().unwrap();
+        line.time = Some(at);
+        line.text.push_str("── ");
+        line.text.push_str(text);
+        line.text.push_str(" ──");
+        self.break_line(start);
+        self.after_cr = false;
+    }
+    fn render_byte(&mut self, byte: u8, offset: u64, at: SystemTime) {
+        if self.lines.back().unwrap().len >= MAX_LINE_BYTES {
+            self.break_line(offset);
+        }
+        match self.display_mode {
+            DisplayMode::Ascii => self.render_text(byte, offset, at, false),
+            DisplayMode::Mixed => self.render_text(byte, offset, at, true),
+            DisplayMode::Hex(_) => self.render_hex(byte, offset, at, true),
+            // laid out from the buffer when drawn, only the lines are tracked
+            DisplayMode::Dump(_) => self.render_hex(byte, offset, at, false),
+        }
+    }
+    /// Renders a byte as text. In the mixed view every byte but printable ASCII is escaped,
+    /// line breaks included.
+    fn render_text(&mut self, byte: u8, offset: u64, at: SystemTime, mixed: bool) {
+        let line = self.lines.back_mut().unwrap();
+        if byte.is_ascii() {
+            self.decoder.flush(&mut line.text);
+        }
+        line.len += 1;
+        let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+        let (newline, hidden) = self.newline.classify(byte, after_cr);
+        if hidden && !mixed {
+            return;
+        }
+        line.time.get_or_insert(at);
+        if mixed && !(byte.is_ascii_graphic() || byte == b' ') {
+            let start = line.text.len();
+            self.hex_escape.push(byte, &mut line.text);
+            line.escapes.push(start..line.text.len());
+        }
+        if newline {
+            self.break_line(offset + 1);
+        } else if mixed {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                line.text.push(char::from(byte));
+            }
+        } else if byte == b'\t' {
+            line.text.push_str("    ");
+        } else {
+            self.decoder.push(byte, &mut line.text);
+        }
+    }
+    /// Renders a byte as hex, `show` off only tracking lines. Lines end after the
+    /// newline byte, or after CR LF together with the `any` newline.
+    fn render_hex(&mut self, byte: u8, offset: u64, at: SystemTime, show: bool) {
+        let after_cr = std::mem::replace(&mut self.after_cr, byte == b'\r');
+        if self.newline == RxNewline::Any && after_cr && byte != b'\n' {
+            self.break_line(offset);
+        }
+        let line = self.lines.back_mut().unwrap();
+        line.len += 1;
+        line.time.get_or_insert(at);
+        if show {
+            push_hex(&mut line.text, byte);
+        }
+        let newline = match self.newline {
+            RxNewline::Lf | RxNewline::Any => byte == b'\n',
+            RxNewline::Cr => byte == b'\r',
+        };
+        if newline {
+            self.break_line(offset + 1);
         }
     }
 }
@@ -174,28 +944,130 @@ fn push_hex(s: &mut String, byte: u8) {
     use std::fmt::Write;
     write!(s, "{byte:02X} ").unwrap();
 }
-fn push_ascii(s: &mut String, byte: u8) {
-    if byte == b'\t' {
-        s.push_str("    ");
+
+/// Byte ranges of the non-overlapping occurrences of `pattern` in `line`.
+/// Case insensitive unless the pattern has uppercase letters.
+fn find_matches(line: &str, pattern: &str) -> Vec<Range<usize>> {
+    let smart_case = !pattern.chars().any(|c| c.is_uppercase());
+    let (haystack, needle) = if smart_case {
+        (line.to_ascii_lowercase(), pattern.to_ascii_lowercase())
     } else {
-        s.push(byte.into());
+        (line.to_string(), pattern.to_string())
+    };
+    haystack
+        .match_indices(&needle)
+        .map(|(i, m)| i..i + m.len())
+        .collect()
+}
+
+/// Ranges of the non-overlapping occurrences of `pattern` in `bytes`. Text is ASCII case
+/// insensitive unless it has uppercase letters, hex is matched exactly.
+fn find_byte_matches(bytes: &[u8], pattern: &BytePattern) -> Vec<Range<usize>> {
+    let smart_case = !pattern.hex && !pattern.bytes.iter().any(u8::is_ascii_uppercase);
+    let pattern = &pattern.bytes[..];
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + pattern.len() <= bytes.len() {
+        let window = &bytes[i..i + pattern.len()];
+        let found = if smart_case {
+            window.eq_ignore_ascii_case(pattern)
+        } else {
+            window == pattern
+        };
+        if found {
+            matches.push(i..i + pattern.len());
+            i += pattern.len();
+        } else {
+            i += 1;
+        }
     }
+    matches
 }
 
 #[derive(Debug, Clone, Copy)]
 enum DisplayMode {
     Ascii,
+    /// printable ASCII as text, everything else escaped
+    Mixed,
     Hex(ByteBuffer),
+    Dump(ByteBuffer),
 }
 
 impl DisplayMode {
     fn clear(&mut self) {
-        if let DisplayMode::Hex(b) = self {
+        if let DisplayMode::Hex(b) | DisplayMode::Dump(b) = self {
             b.buf = None;
         }
     }
 }
 
+/// Views of a console, in the order `switch_view` cycles through them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Text,
+    /// printable ASCII as text, everything else escaped
+    Mixed,
+    Hex,
+    Hexdump,
+}
+
+pub fn parse_view(s: &str) -> Result<View, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "text" | "ascii" => Ok(View::Text),
+        "mixed" => Ok(View::Mixed),
+        "hex" => Ok(View::Hex),
+        "hexdump" | "dump" => Ok(View::Hexdump),
+        _ => Err(format!(
+            "invalid view `{s}`, expected text, mixed, hex or hexdump"
+        )),
+    }
+}
+
+/// Bytes per row of the hexdump view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpWidth {
+    /// as many as fit the console, at least 8
+    Auto,
+    Fixed(usize),
+}
+
+impl DumpWidth {
+    pub const ALL: &'static [DumpWidth] =
+        &[Self::Auto, Self::Fixed(8), Self::Fixed(16), Self::Fixed(32)];
+    /// Bytes to lay out in each row of a console `columns` wide
+    pub fn bytes_per_row(self, columns: usize) -> usize {
+        match self {
+            Self::Fixed(n) => n,
+            Self::Auto => [32, 16, 8]
+                .into_iter()
+                .find(|&n| Self::row_width(n) <= columns)
+                .unwrap_or(8),
+        }
+    }
+    /// Columns taken by a row of `n` bytes: offset, hex groups of 8 and ASCII gutter
+    pub fn row_width(n: usize) -> usize {
+        8 + 2 + 3 * n + n.div_ceil(8) - 1 + 2 + n + 1
+    }
+    pub fn name(self) -> String {
+        match self {
+            Self::Auto => String::from("auto"),
+            Self::Fixed(n) => n.to_string(),
+        }
+    }
+}
+
+pub fn parse_dump_width(s: &str) -> Result<DumpWidth, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "auto" => Ok(DumpWidth::Auto),
+        "8" => Ok(DumpWidth::Fixed(8)),
+        "16" => Ok(DumpWidth::Fixed(16)),
+        "32" => Ok(DumpWidth::Fixed(32)),
+        _ => Err(format!(
+            "invalid hexdump width `{s}`, expected auto, 8, 16 or 32"
+        )),
+    }
+}
+
 /// Struct for buffering nibbles to output bytes
 #[derive(Debug, Clone, Copy)]
 struct ByteBuffer {
@@ -213,40 +1085,94 @@ impl ByteBuffer {
     }
 }
 
-/// Helper struct to add cursor only when rendering
-pub struct WithCursor<'a> {
-    s: &'a mut String,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl<'a> WithCursor<'a> {
-    pub fn new(s: &'a mut String, cursor: char) -> Self {
-        s.push(cursor);
-        Self { s }
+    fn push_lines(display: &mut Display, lines: std::ops::Range<usize>) {
+        for i in lines {
+            display.push_bytes(format!("line {i}\n").as_bytes());
+        }
     }
-}
-impl<'a> Drop for WithCursor<'a> {
-    fn drop(&mut self) {
-        self.s.pop();
+
+    #[test]
+    fn scrollback_drops_oldest_lines() {
+        let mut display = Display::new();
+        display.scrollback = 5;
+        push_lines(&mut display, 0..10);
+        // "line 6" to "line 9" and the empty line being received
+        assert_eq!(display.lines().len(), 5);
+        assert_eq!(display.lines()[0].text, "line 6");
+        assert_eq!(display.base(), 6 * 7);
+        assert_eq!(display.bytes().len(), 4 * 7);
     }
-}
 
-impl<'a> AsRef<String> for WithCursor<'a> {
-    fn as_ref(&self) -> &String {
-        self.s
+    #[test]
+    fn layout_survives_eviction() {
+        let mut display = Display::new();
+        display.scrollback = 5;
+        push_lines(&mut display, 0..3);
+        // every line takes 2 rows of 4 columns
+        assert_eq!(display.layout(4), 3 * 2 + 1);
+        push_lines(&mut display, 3..10);
+        assert_eq!(display.layout(4), 4 * 2 + 1);
+        assert_eq!(display.line_row(0), 0);
+        assert_eq!(display.line_row(1), 2);
+        assert_eq!(display.line_row(4), 8);
+        assert_eq!(display.line_at_row(0), 0);
+        assert_eq!(display.line_at_row(3), 1);
+        assert_eq!(display.line_at_row(8), 4);
     }
-}
-impl<'a> AsRef<str> for WithCursor<'a> {
-    fn as_ref(&self) -> &str {
-        self.s
+
+    #[test]
+    fn layout_wraps_wide_chars_as_drawn() {
+        let mut display = Display::new();
+        // 10 columns, but a wide character never straddles rows of 5: 日本|語日|本
+        display.push_bytes("日本語日本\nx\n".as_bytes());
+        assert_eq!(display.layout(5), 3 + 1 + 1);
+        assert_eq!(display.line_row(1), 3);
+        assert_eq!(display.line_at_row(2), 0);
+        assert_eq!(display.line_at_row(3), 1);
+        assert_eq!(display.find(Some("本")), 2);
+        assert_eq!(display.match_row(0), 0);
+        assert_eq!(display.match_row(1), 2);
     }
-}
-impl<'a> AsMut<String> for WithCursor<'a> {
-    fn as_mut(&mut self) -> &mut String {
-        self.s
+
+    #[test]
+    fn matches_survive_eviction() {
+        let mut display = Display::new();
+        display.scrollback = 5;
+        push_lines(&mut display, 0..3);
+        assert_eq!(display.find(Some("line")), 3);
+        push_lines(&mut display, 3..10);
+        assert_eq!(display.find(Some("line")), 4);
+        assert_eq!(display.nth_match(0), (0, 0..4));
+        let (line, _) = display.nth_match(3);
+        assert_eq!(display.lines()[line].text, "line 9");
+        assert_eq!(display.first_match_from(2), 2);
     }
-}
-impl<'a> AsMut<str> for WithCursor<'a> {
-    fn as_mut(&mut self) -> &mut str {
-        self.s
+
+    #[test]
+    fn byte_limit_drops_long_lines() {
+        let mut display = Display::new();
+        display.byte_limit = 5000;
+        display.push_bytes(&[b'x'; 10_000]);
+        // split in lines of 4096 bytes, only the last one fits
+        assert_eq!(display.lines().len(), 1);
+        assert_eq!(display.base(), 2 * MAX_LINE_BYTES as u64);
+        assert_eq!(display.bytes().len(), 10_000 - 2 * MAX_LINE_BYTES);
+    }
+
+    #[test]
+    fn pending_nibble_stays_out_of_the_text() {
+        let mut display = Display::new();
+        display.set_view(View::Hex);
+        assert_eq!(display.push_char(b'4'), None);
+        assert_eq!(display.pending_nibble(), Some(4));
+        // e.g. a macro sent before the low nibble is typed
+        display.push_bytes(b"\x01");
+        assert_eq!(display.push_char(b'1'), Some(0x41));
+        assert_eq!(display.pending_nibble(), None);
+        assert_eq!(display.lines().back().unwrap().text, "01 41 ");
     }
 }
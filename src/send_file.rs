@@ -0,0 +1,127 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// File being streamed to the port a little on every update, so RX keeps running
+pub struct FileSend {
+    pub path: PathBuf,
+    data: Vec<u8>,
+    pos: usize,
+    char_delay: Duration,
+    line_delay: Duration,
+    /// when the next byte may go out
+    next_at: Instant,
+}
+
+impl FileSend {
+    pub fn new(path: PathBuf, char_delay: Duration, line_delay: Duration) -> io::Result<Self> {
+        let data = fs::read(&path)?;
+        Ok(Self {
+            path,
+            data,
+            pos: 0,
+            char_delay,
+            line_delay,
+            next_at: Instant::now(),
+        })
+    }
+    pub fn sent(&self) -> usize {
+        self.pos
+    }
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    pub fn is_done(&self) -> bool {
+        self.pos == self.data.len()
+    }
+    /// When the next byte is due
+    pub fn next_at(&self) -> Instant {
+        self.next_at
+    }
+    /// Writes the bytes due by now, at most what `baud_rate` can carry in a frame,
+    /// returning them
+    pub fn poll(&mut self, port: &mut dyn Write, baud_rate: u32) -> io::Result<&[u8]> {
+        // ~10 bits per byte, 60 updates per second
+        let max = (baud_rate as usize / 600).max(16);
+        let now = Instant::now();
+        let start = self.pos;
+        let mut end = start;
+        while end < self.data.len() && end - start < max && self.next_at <= now {
+            let delay = if self.data[end] == b'\n' && !self.line_delay.is_zero() {
+                self.line_delay
+            } else {
+                self.char_delay
+            };
+            // no burst to catch up after a stall
+            self.next_at = self.next_at.max(now) + delay;
+            end += 1;
+        }
+        if end == start {
+            return Ok(&[]);
+        }
+        port.write_all(&self.data[start..end])?;
+        self.pos = end;
+        Ok(&self.data[start..end])
+    }
+}
+
+/// Completes the last component of a typed path, returning the completed input and,
+/// when still ambiguous, the candidates
+pub fn complete_path(input: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => input.split_at(i + 1),
+        None => ("", input),
+    };
+    let dir_path = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir)
+    };
+    let mut names = fs::read_dir(dir_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some(if entry.path().is_dir() {
+                name + "/"
+            } else {
+                name
+            })
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    let Some(first) = names.first() else {
+        return (input.to_string(), names);
+    };
+    let common = names.iter().fold(first.len(), |len, name| {
+        first
+            .chars()
+            .zip(name.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum::<usize>()
+            .min(len)
+    });
+    let completed = format!("{dir}{}", &first[..common]);
+    if names.len() == 1 {
+        names.clear();
+    }
+    (completed, names)
+}
+
+/// Expands a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,255 @@
+use crate::dummy::DummySerial;
+use serialport::{
+    DataBits, FlowControl, Parity, SerialPort, SerialPortBuilder, SerialPortType, StopBits,
+};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+/// Common baud rates, used when cycling through rates in the config popup
+pub static BAUD_RATES: &[u32] = &[
+    300, 1200, 2400, 4800, 9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600, 1000000,
+    2000000, 3000000,
+];
+
+/// Full serial line configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+}
+
+impl LineConfig {
+    /// Builder for opening `path` with this configuration
+    pub fn builder(&self, path: &str) -> SerialPortBuilder {
+        serialport::new(path, self.baud_rate)
+            .data_bits(self.data_bits)
+            .parity(self.parity)
+            .stop_bits(self.stop_bits)
+            .flow_control(self.flow_control)
+            .timeout(Duration::from_millis(500))
+    }
+    /// Applies every setting to an already open port
+    pub fn apply(&self, port: &mut dyn SerialPort) -> serialport::Result<()> {
+        port.set_baud_rate(self.baud_rate)?;
+        port.set_data_bits(self.data_bits)?;
+        port.set_parity(self.parity)?;
+        port.set_stop_bits(self.stop_bits)?;
+        port.set_flow_control(self.flow_control)
+    }
+}
+
+/// Opens `path` with `line`, `dummy` being a loopback port
+pub fn open(path: &str, line: &LineConfig) -> serialport::Result<Box<dyn SerialPort>> {
+    if path == "dummy" {
+        let mut port = Box::new(DummySerial::new(line.baud_rate));
+        line.apply(port.as_mut())?;
+        Ok(port)
+    } else {
+        line.builder(path).open()
+    }
+}
+
+/// What identifies an open port, to find it again after it was unplugged
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortId {
+    pub path: String,
+    /// serial number of a USB adapter, which may come back under another path
+    pub usb_serial: Option<String>,
+}
+
+impl PortId {
+    /// Identity of the port at `path`, looked up among the ports of the system
+    pub fn of(path: &str) -> Self {
+        let usb_serial = serialport::available_ports()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|p| same_path(&p.port_name, path))
+            .and_then(|p| match p.port_type {
+                SerialPortType::UsbPort(usb) => usb.serial_number,
+                _ => None,
+            });
+        Self {
+            path: path.to_string(),
+            usb_serial,
+        }
+    }
+    /// Path the port can be opened from, `None` while it is missing. A USB adapter with a
+    /// serial number is found by it wherever it comes back, any other port by its path.
+    pub fn find(&self) -> Option<String> {
+        match &self.usb_serial {
+            Some(sn) => serialport::available_ports()
+                .ok()?
+                .into_iter()
+                .find(|p| {
+                    matches!(&p.port_type, SerialPortType::UsbPort(usb)
+                        if usb.serial_number.as_ref() == Some(sn))
+                })
+                .map(|p| p.port_name),
+            None => Path::new(&self.path).exists().then(|| self.path.clone()),
+        }
+    }
+}
+
+/// Whether two paths name the same device, e.g. through a `/dev/serial/by-id` link
+fn same_path(a: &str, b: &str) -> bool {
+    match (Path::new(a).canonicalize(), Path::new(b).canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Formats as e.g. `115200 8N1 RTS/CTS`
+impl fmt::Display for LineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{}{}",
+            self.baud_rate,
+            data_bits_str(self.data_bits),
+            parity_char(self.parity),
+            stop_bits_str(self.stop_bits)
+        )?;
+        match self.flow_control {
+            FlowControl::None => Ok(()),
+            fc => write!(f, " {}", flow_control_str(fc)),
+        }
+    }
+}
+
+pub fn data_bits_str(d: DataBits) -> &'static str {
+    match d {
+        DataBits::Five => "5",
+        DataBits::Six => "6",
+        DataBits::Seven => "7",
+        DataBits::Eight => "8",
+    }
+}
+pub fn parity_char(p: Parity) -> char {
+    match p {
+        Parity::None => 'N',
+        Parity::Odd => 'O',
+        Parity::Even => 'E',
+    }
+}
+pub fn parity_str(p: Parity) -> &'static str {
+    match p {
+        Parity::None => "none",
+        Parity::Odd => "odd",
+        Parity::Even => "even",
+    }
+}
+pub fn stop_bits_str(s: StopBits) -> &'static str {
+    match s {
+        StopBits::One => "1",
+        StopBits::Two => "2",
+    }
+}
+pub fn flow_control_str(fc: FlowControl) -> &'static str {
+    match fc {
+        FlowControl::None => "none",
+        FlowControl::Software => "XON/XOFF",
+        FlowControl::Hardware => "RTS/CTS",
+    }
+}
+
+pub fn parse_data_bits(s: &str) -> Result<DataBits, String> {
+    match s {
+        "5" => Ok(DataBits::Five),
+        "6" => Ok(DataBits::Six),
+        "7" => Ok(DataBits::Seven),
+        "8" => Ok(DataBits::Eight),
+        _ => Err(format!("invalid data bits `{s}`, expected 5, 6, 7 or 8")),
+    }
+}
+pub fn parse_parity(s: &str) -> Result<Parity, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "n" | "none" => Ok(Parity::None),
+        "o" | "odd" => Ok(Parity::Odd),
+        "e" | "even" => Ok(Parity::Even),
+        _ => Err(format!("invalid parity `{s}`, expected none, odd or even")),
+    }
+}
+pub fn parse_stop_bits(s: &str) -> Result<StopBits, String> {
+    match s {
+        "1" => Ok(StopBits::One),
+        "2" => Ok(StopBits::Two),
+        _ => Err(format!("invalid stop bits `{s}`, expected 1 or 2")),
+    }
+}
+pub fn parse_flow_control(s: &str) -> Result<FlowControl, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "none" => Ok(FlowControl::None),
+        "software" | "sw" | "xon" | "xonxoff" => Ok(FlowControl::Software),
+        "hardware" | "hw" | "rts" | "rtscts" => Ok(FlowControl::Hardware),
+        _ => Err(format!(
+            "invalid flow control `{s}`, expected none, software or hardware"
+        )),
+    }
+}
+
+/// Picks the next (or previous) common baud rate relative to `current`
+pub fn cycle_baud_rate(current: u32, forward: bool) -> u32 {
+    let next = if forward {
+        BAUD_RATES.iter().find(|&&b| b > current)
+    } else {
+        BAUD_RATES.iter().rev().find(|&&b| b < current)
+    };
+    next.copied().unwrap_or(current)
+}
+pub fn cycle_data_bits(d: DataBits, forward: bool) -> DataBits {
+    use DataBits::*;
+    cycle(&[Five, Six, Seven, Eight], d, forward)
+}
+pub fn cycle_parity(p: Parity, forward: bool) -> Parity {
+    cycle(&[Parity::None, Parity::Odd, Parity::Even], p, forward)
+}
+pub fn cycle_stop_bits(s: StopBits, forward: bool) -> StopBits {
+    cycle(&[StopBits::One, StopBits::Two], s, forward)
+}
+pub fn cycle_flow_control(fc: FlowControl, forward: bool) -> FlowControl {
+    use FlowControl::*;
+    cycle(&[None, Software, Hardware], fc, forward)
+}
+
+/// Returns the element after (or before) `current` in `values`, wrapping around
+pub fn cycle<T: Copy + PartialEq>(values: &[T], current: T, forward: bool) -> T {
+    let idx = values.iter().position(|&v| v == current).unwrap_or(0);
+    let next = if forward {
+        (idx + 1) % values.len()
+    } else {
+        (idx + values.len() - 1) % values.len()
+    };
+    values[next]
+}
+
+pub fn parse_level(s: &str) -> Result<bool, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "1" | "on" | "high" | "true" => Ok(true),
+        "0" | "off" | "low" | "false" => Ok(false),
+        _ => Err(format!("invalid line level `{s}`, expected on or off")),
+    }
+}
+
+/// Levels of the modem status input lines, `None` when the port can't report it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ModemStatus {
+    pub cts: Option<bool>,
+    pub dsr: Option<bool>,
+    pub dcd: Option<bool>,
+    pub ri: Option<bool>,
+}
+
+impl ModemStatus {
+    pub fn poll(port: &mut dyn SerialPort) -> Self {
+        Self {
+            cts: port.read_clear_to_send().ok(),
+            dsr: port.read_data_set_ready().ok(),
+            dcd: port.read_carrier_detect().ok(),
+            ri: port.read_ring_indicator().ok(),
+        }
+    }
+}
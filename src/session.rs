@@ -0,0 +1,90 @@
+//! One open port with its consoles, control lines and log
+
+use crate::events::PortIo;
+use crate::logger::Logger;
+use crate::screen::{Rx, Tx};
+use crate::send_file::FileSend;
+use crate::serial::{self, LineConfig, ModemStatus, PortId};
+use serialport::SerialPort;
+use std::io;
+use std::time::Instant;
+
+pub struct Session {
+    /// port for settings and control lines, data goes through `io`
+    pub serial: Box<dyn SerialPort>,
+    pub io: PortIo,
+    /// what the port was opened from, to open it again
+    pub port_id: PortId,
+    /// the port went away, e.g. a USB adapter was unplugged
+    pub disconnected: bool,
+    pub next_reconnect: Instant,
+    pub line: LineConfig,
+    /// output control lines, as last written
+    pub dtr: bool,
+    pub rts: bool,
+    /// input control lines, polled every `App::MODEM_POLL`
+    pub modem: ModemStatus,
+    pub next_modem_poll: Instant,
+    /// end of the BREAK condition being sent
+    pub break_until: Option<Instant>,
+    pub tx: Tx,
+    pub rx: Rx,
+    pub logger: Logger,
+    /// file being sent
+    pub sending: Option<FileSend>,
+}
+
+impl Session {
+    pub fn new(
+        serial: Box<dyn SerialPort>,
+        port_id: PortId,
+        io: PortIo,
+        line: LineConfig,
+        logger: Logger,
+    ) -> Self {
+        Self {
+            serial,
+            io,
+            port_id,
+            disconnected: false,
+            next_reconnect: Instant::now(),
+            line,
+            dtr: true,
+            rts: true,
+            modem: ModemStatus::default(),
+            next_modem_poll: Instant::now(),
+            break_until: None,
+            tx: Tx::new(),
+            rx: Rx::new(),
+            logger,
+            sending: None,
+        }
+    }
+    /// Short name of the port for tabs and the timeline
+    pub fn name(&self) -> &str {
+        port_name(&self.port_id.path)
+    }
+    /// Opens the port at `path` with the current line settings and control lines, moving
+    /// the I/O threads over to it. The threads are stopped first, as they hold on to the
+    /// port, and left stopped if it can't be opened.
+    pub fn open_port(&mut self, path: &str) -> serialport::Result<Box<dyn SerialPort>> {
+        self.io.stop();
+        let mut port = serial::open(path, &self.line)?;
+        // not every port has control lines, e.g. a pty
+        let _ = port.write_data_terminal_ready(self.dtr);
+        let _ = port.write_request_to_send(self.rts);
+        self.io.restart(port.as_ref())?;
+        Ok(port)
+    }
+    /// Records a change of the port in both consoles and the log
+    pub fn push_event(&mut self, event: &str) -> io::Result<()> {
+        self.tx.display.push_event(event);
+        self.rx.display.push_event(event);
+        self.logger.event(event)
+    }
+}
+
+/// Short name of the port at `path`, without `/dev/`
+pub fn port_name(path: &str) -> &str {
+    path.strip_prefix("/dev/").unwrap_or(path)
+}
@@ -0,0 +1,124 @@
+//! RX of every open port merged into one console, a line at a time in the order lines
+//! complete
+
+use crate::clock::TimestampMode;
+use crate::decode::Decoder;
+use crate::screen::Display;
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+/// Longest line put together before it is pushed anyway, as in the consoles
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Line being received on a port
+#[derive(Debug, Default)]
+struct Pending {
+    bytes: Vec<u8>,
+    /// arrival time of the first byte
+    time: Option<SystemTime>,
+    after_cr: bool,
+}
+
+/// Port of each line of the timeline, by the stream offset the line starts at
+#[derive(Debug, Default)]
+pub struct Sources(VecDeque<(u64, usize)>);
+
+impl Sources {
+    /// Port of the line starting at stream offset `start`
+    pub fn at(&self, start: u64) -> Option<usize> {
+        let n = self.0.partition_point(|&(offset, _)| offset <= start);
+        Some(self.0.get(n.checked_sub(1)?)?.1)
+    }
+}
+
+pub struct Timeline {
+    /// lines of every port, each prefixed with the port name and stamped with the arrival
+    /// of its first byte
+    pub display: Display,
+    pub sources: Sources,
+    /// line being received on each port, by port index
+    pending: Vec<Pending>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        let mut display = Display::new();
+        display.timestamps = TimestampMode::Absolute;
+        Self {
+            display,
+            sources: Sources::default(),
+            pending: Vec::new(),
+        }
+    }
+    /// Adds what the `port`th port, called `name`, received. Lines are broken and decoded
+    /// the way its RX console `rx` shows text.
+    pub fn receive(&mut self, port: usize, name: &str, data: &[u8], rx: &Display) {
+        if self.pending.len() <= port {
+            self.pending.resize_with(port + 1, Pending::default);
+        }
+        let now = SystemTime::now();
+        for &b in data {
+            let pending = &mut self.pending[port];
+            let after_cr = std::mem::replace(&mut pending.after_cr, b == b'\r');
+            let (newline, hidden) = rx.newline().classify(b, after_cr);
+            if hidden {
+                continue;
+            }
+            pending.time.get_or_insert(now);
+            if !newline {
+                pending.bytes.push(b);
+            }
+            if newline || pending.bytes.len() >= MAX_LINE_BYTES {
+                let bytes = std::mem::take(&mut pending.bytes);
+                let time = pending.time.take().unwrap_or(now);
+                let text = decode(&bytes, rx);
+                self.push_line(port, name, &text, time);
+            }
+        }
+    }
+    /// Records an event of the `port`th port, e.g. a disconnect, on a line of its own
+    pub fn push_event(&mut self, port: usize, name: &str, text: &str) {
+        self.push_line(port, name, &format!("── {text} ──"), SystemTime::now());
+    }
+    pub fn clear(&mut self) {
+        self.display.clear();
+        self.sources.0.clear();
+    }
+    fn push_line(&mut self, port: usize, name: &str, text: &str, at: SystemTime) {
+        self.sources.0.push_back((self.display.end(), port));
+        let line = format!("{name} │ {text}\n");
+        self.display.push_bytes_at(line.as_bytes(), at);
+        // the line holding the first kept byte gives its port
+        let base = self.display.base();
+        while self
+            .sources
+            .0
+            .get(1)
+            .is_some_and(|&(offset, _)| offset <= base)
+        {
+            self.sources.0.pop_front();
+        }
+    }
+}
+
+/// Text of a line of received bytes, with the encoding and control characters of `rx`
+fn decode(bytes: &[u8], rx: &Display) -> String {
+    let mut decoder = Decoder::new(rx.encoding(), rx.control_style());
+    let mut text = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if b == b'\t' {
+            decoder.flush(&mut text);
+            text.push('\t');
+        } else {
+            decoder.push(b, &mut text);
+        }
+    }
+    decoder.flush(&mut text);
+    text
+}
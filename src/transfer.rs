@@ -0,0 +1,441 @@
+use crate::{xmodem, zmodem};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// File transfer protocols
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    /// 128 byte blocks with an arithmetic checksum
+    Xmodem,
+    /// 128 byte blocks with a CRC-16
+    XmodemCrc,
+    /// 1024 byte blocks with a CRC-16
+    Xmodem1k,
+    /// batch of files, with names and sizes, over XMODEM-1K
+    Ymodem,
+    Zmodem,
+}
+
+impl Protocol {
+    pub const ALL: &'static [Protocol] = &[
+        Self::Xmodem,
+        Self::XmodemCrc,
+        Self::Xmodem1k,
+        Self::Ymodem,
+        Self::Zmodem,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Xmodem => "XMODEM",
+            Self::XmodemCrc => "XMODEM-CRC",
+            Self::Xmodem1k => "XMODEM-1K",
+            Self::Ymodem => "YMODEM",
+            Self::Zmodem => "ZMODEM",
+        }
+    }
+    /// Whether the protocol carries file names, so it receives into a directory and
+    /// sends several files
+    pub fn is_batch(self) -> bool {
+        matches!(self, Self::Ymodem | Self::Zmodem)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Send,
+    Receive,
+}
+
+impl Direction {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Send => "send",
+            Self::Receive => "receive",
+        }
+    }
+}
+
+/// Transfer asked for from the transfer popup
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub protocol: Protocol,
+    pub direction: Direction,
+    /// files to send, or where to receive: a file for XMODEM, a directory otherwise
+    pub paths: Vec<PathBuf>,
+}
+
+/// State of the running transfer, as shown while it runs
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    /// file being transferred
+    pub file: String,
+    /// bytes of the file transferred so far
+    pub bytes: u64,
+    /// size of the file, when known
+    pub total: Option<u64>,
+    /// files completed
+    pub files: Vec<(String, u64)>,
+    /// blocks sent again or rejected
+    pub retries: usize,
+}
+
+impl Progress {
+    pub fn start_file(&mut self, name: &str, total: Option<u64>) {
+        self.file = name.to_string();
+        self.bytes = 0;
+        self.total = total;
+    }
+    pub fn end_file(&mut self) {
+        self.files
+            .push((std::mem::take(&mut self.file), self.bytes));
+        self.total = None;
+    }
+}
+
+/// Outcome of a successful transfer
+#[derive(Debug, Clone)]
+pub struct Summary {
+    pub files: Vec<(String, u64)>,
+    pub retries: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.files.is_empty() {
+            write!(f, "no files")?;
+        }
+        for (i, (name, bytes)) in self.files.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{name} ({bytes} bytes)")?;
+        }
+        let bytes = self.files.iter().map(|(_, b)| b).sum::<u64>();
+        let secs = self.elapsed.as_secs_f64();
+        write!(
+            f,
+            " in {secs:.1} s ({:.0} B/s), {} retries",
+            bytes as f64 / secs.max(0.001),
+            self.retries
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("cancelled by the other side")]
+    RemoteCancelled,
+    #[error("timed out {0}")]
+    Timeout(&'static str),
+    #[error("{0}")]
+    Protocol(String),
+}
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Port a transfer runs over
+pub trait Channel {
+    /// Appends what was received to `buf`, waiting at most `timeout` for something to come
+    fn recv(&mut self, buf: &mut VecDeque<u8>, timeout: Duration) -> Result<()>;
+    fn send(&mut self, data: &[u8]) -> Result<()>;
+}
+
+/// Port owned by a transfer. Reads never block for long, so that progress gets
+/// reported while waiting for the other side.
+pub struct Link<'a> {
+    io: &'a mut dyn Channel,
+    input: VecDeque<u8>,
+    report: &'a mut dyn FnMut(&Progress),
+    last_report: Instant,
+    pub progress: Progress,
+}
+
+impl<'a> Link<'a> {
+    const REPORT_INTERVAL: Duration = Duration::from_millis(50);
+
+    fn new(io: &'a mut dyn Channel, report: &'a mut dyn FnMut(&Progress)) -> Self {
+        Self {
+            io,
+            input: VecDeque::new(),
+            report,
+            last_report: Instant::now(),
+            progress: Progress::default(),
+        }
+    }
+    /// Next received byte, `None` if nothing came within `timeout`
+    pub fn read(&mut self, timeout: Duration) -> Result<Option<u8>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(b) = self.input.pop_front() {
+                return Ok(Some(b));
+            }
+            self.tick();
+            let left = deadline.saturating_duration_since(Instant::now());
+            self.io
+                .recv(&mut self.input, left.min(Self::REPORT_INTERVAL))?;
+            if self.input.is_empty() && left.is_zero() {
+                return Ok(None);
+            }
+        }
+    }
+    /// Whether bytes are waiting to be read
+    pub fn has_input(&mut self) -> Result<bool> {
+        self.io.recv(&mut self.input, Duration::ZERO)?;
+        Ok(!self.input.is_empty())
+    }
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.tick();
+        self.io.send(data)
+    }
+    /// Drops input until the line stays quiet for `quiet`
+    pub fn purge(&mut self, quiet: Duration) -> Result<()> {
+        self.input.clear();
+        while self.read(quiet)?.is_some() {}
+        Ok(())
+    }
+    /// Reports progress if it is due
+    pub fn tick(&mut self) {
+        if self.last_report.elapsed() >= Self::REPORT_INTERVAL {
+            self.last_report = Instant::now();
+            (self.report)(&self.progress);
+        }
+    }
+}
+
+/// Runs a transfer over `io`, calling `report` regularly with its progress
+pub fn run(
+    io: &mut dyn Channel,
+    request: &Request,
+    report: &mut dyn FnMut(&Progress),
+) -> Result<Summary> {
+    let start = Instant::now();
+    let mut link = Link::new(io, report);
+    let res = match (request.protocol, request.direction) {
+        (Protocol::Zmodem, Direction::Send) => zmodem::send(&mut link, &request.paths),
+        (Protocol::Zmodem, Direction::Receive) => zmodem::receive(&mut link, &request.paths[0]),
+        (p, Direction::Send) => xmodem::send(&mut link, p, &request.paths),
+        (p, Direction::Receive) => xmodem::receive(&mut link, p, &request.paths[0]),
+    };
+    match res {
+        Ok(()) => Ok(Summary {
+            files: link.progress.files,
+            retries: link.progress.retries,
+            elapsed: start.elapsed(),
+        }),
+        Err(e) => {
+            // tell the other side to give up, in terms every protocol understands
+            if !matches!(e, Error::RemoteCancelled) {
+                let _ = link.io.send(&[[0x18; 8], [0x08; 8]].concat());
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Name sent for a file, without its directories
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Modification time of a file, in seconds since the epoch
+pub fn mtime(path: &Path) -> u64 {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Where to store a received file named `name` in `dir`. Only the last component of the
+/// name is used, and existing files are never overwritten.
+pub fn receive_path(dir: &Path, name: &str) -> PathBuf {
+    let name = name
+        .rsplit(['/', '\\'])
+        .find(|s| !s.is_empty() && *s != "." && *s != "..")
+        .unwrap_or("received");
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    (1..)
+        .map(|n| dir.join(format!("{name}.{n}")))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+/// CRC-16/XMODEM of `data`, continuing from `crc`
+pub fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &b in data {
+        crc ^= (b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// CRC-32 (IEEE) register after `data`, continuing from `crc`. Start from `0xFFFFFFFF` and
+/// complement the result.
+pub fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+    use std::thread;
+
+    #[test]
+    fn check_values() {
+        assert_eq!(crc16(0, b"123456789"), 0x31C3);
+        assert_eq!(!crc32(!0, b"123456789"), 0xCBF4_3926);
+    }
+
+    /// One end of an in-memory line. The byte sent at offset `corrupt` gets flipped.
+    struct Loopback {
+        tx: Sender<Vec<u8>>,
+        rx: Receiver<Vec<u8>>,
+        sent: usize,
+        corrupt: Option<usize>,
+    }
+
+    fn loopback(corrupt: Option<usize>) -> (Loopback, Loopback) {
+        let (a_tx, b_rx) = mpsc::channel();
+        let (b_tx, a_rx) = mpsc::channel();
+        let a = Loopback {
+            tx: a_tx,
+            rx: a_rx,
+            sent: 0,
+            corrupt,
+        };
+        let b = Loopback {
+            tx: b_tx,
+            rx: b_rx,
+            sent: 0,
+            corrupt: None,
+        };
+        (a, b)
+    }
+
+    impl Channel for Loopback {
+        fn recv(&mut self, buf: &mut VecDeque<u8>, timeout: Duration) -> Result<()> {
+            match self.rx.recv_timeout(timeout) {
+                Ok(data) => buf.extend(data),
+                Err(RecvTimeoutError::Timeout) => (),
+                // the other side is done
+                Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+            }
+            while let Ok(data) = self.rx.try_recv() {
+                buf.extend(data);
+            }
+            Ok(())
+        }
+        fn send(&mut self, data: &[u8]) -> Result<()> {
+            let mut data = data.to_vec();
+            if let Some(i) = self.corrupt.and_then(|at| at.checked_sub(self.sent)) {
+                if let Some(b) = data.get_mut(i) {
+                    *b ^= 0x55;
+                    self.corrupt = None;
+                }
+            }
+            self.sent += data.len();
+            let _ = self.tx.send(data);
+            Ok(())
+        }
+    }
+
+    /// Empty directory for a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tuicom-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Sends a file with `protocol` from one end of a loopback to the other, corrupting
+    /// one byte on the way, and returns what was received with the retries of both ends
+    fn transfer(protocol: Protocol, name: &str, data: &[u8]) -> (Vec<u8>, usize) {
+        let dir = test_dir(name);
+        let source = dir.join("source.bin");
+        fs::write(&source, data).unwrap();
+        let received = dir.join("received");
+        fs::create_dir(&received).unwrap();
+        let target = match protocol.is_batch() {
+            true => received.clone(),
+            false => received.join("source.bin"),
+        };
+        let (mut sender, mut receiver) = loopback(Some(3000));
+        let send = thread::spawn(move || {
+            let request = Request {
+                protocol,
+                direction: Direction::Send,
+                paths: vec![source],
+            };
+            run(&mut sender, &request, &mut |_| ())
+        });
+        let request = Request {
+            protocol,
+            direction: Direction::Receive,
+            paths: vec![target],
+        };
+        let received_summary = run(&mut receiver, &request, &mut |_| ()).unwrap();
+        let sent_summary = send.join().unwrap().unwrap();
+        let bytes = fs::read(received.join("source.bin")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        (bytes, sent_summary.retries + received_summary.retries)
+    }
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 256) as u8).collect()
+    }
+
+    #[test]
+    fn xmodem_1k_loopback() {
+        let data = test_data(10 * 1024);
+        let (received, retries) = transfer(Protocol::Xmodem1k, "xmodem-1k", &data);
+        // XMODEM pads the last block
+        assert_eq!(&received[..data.len()], &data[..]);
+        assert!(retries > 0);
+    }
+
+    #[test]
+    fn ymodem_loopback() {
+        let data = test_data(10_000);
+        let (received, retries) = transfer(Protocol::Ymodem, "ymodem", &data);
+        assert_eq!(received, data);
+        assert!(retries > 0);
+    }
+
+    #[test]
+    fn zmodem_loopback() {
+        let data = test_data(10_000);
+        let (received, retries) = transfer(Protocol::Zmodem, "zmodem", &data);
+        assert_eq!(received, data);
+        assert!(retries > 0);
+    }
+}
@@ -1,4 +1,16 @@
-use crate::app::{App, Mode};
+use crate::app::{
+    App, BaudForm, ConfigMenu, MacroEditor, MacroMenu, Mode, Pane, PortLayout, SendFileForm,
+    Setting, TransferForm,
+};
+use crate::clock::TimestampMode;
+use crate::keymap::{Action, Keymap};
+use crate::keys;
+use crate::line_edit::SendMode;
+use crate::picker::{self, PortPicker};
+use crate::screen::{Display, LineWrap, Scroll};
+use crate::timeline::Timeline;
+use crate::transfer::{self, Progress, Request};
+use crate::vt::{Cell, Vt};
 use itertools::Itertools;
 use ratatui::{
     backend::Backend,
@@ -6,29 +18,78 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Span, Spans},
     widgets::Clear,
-    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+    widgets::{
+        Block, BorderType, Borders, Gauge, List, ListItem, ListState, Paragraph, Tabs, Wrap,
+    },
     Frame,
 };
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    if app.mode.is_terminal() {
+        return draw_terminal(f, app);
+    }
+    let mut area = f.size();
+    // a tab per port
+    if app.sessions.len() > 1 {
+        draw_port_tabs(f, app, Rect { height: 1, ..area });
+        area.y += 1;
+        area.height = area.height.saturating_sub(1);
+    }
+    // tiles leave more room to the RX of every port
+    let (tx_height, rx_height) = match app.layout {
+        PortLayout::Tiles if app.sessions.len() > 1 && !app.show_timeline => (30, 66),
+        _ => (48, 48),
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Percentage(48),
-            Constraint::Percentage(48),
+            Constraint::Percentage(tx_height),
+            Constraint::Percentage(rx_height),
             Constraint::Min(1),
             Constraint::Length(1),
         ])
-        .split(f.size());
+        .split(area);
 
-    // tx
-    draw_tx(f, app, chunks[0]);
+    // tx of the current port, with the line editor below it in line mode
+    if app.send_mode == SendMode::Line {
+        let tx_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)])
+            .split(chunks[0]);
+        draw_tx(f, app, tx_chunks[0]);
+        draw_line_editor(f, app, tx_chunks[1]);
+    } else {
+        draw_tx(f, app, chunks[0]);
+    }
 
-    // rx
-    draw_rx(f, app, chunks[1]);
+    // rx of the current port, of every port in tiles, or the timeline merging them
+    if app.show_timeline {
+        draw_timeline(f, app, chunks[1]);
+    } else if app.layout == PortLayout::Tiles && app.sessions.len() > 1 {
+        let tiles = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Ratio(1, app.sessions.len() as u32);
+                app.sessions.len()
+            ])
+            .split(chunks[1]);
+        for (port, &tile) in tiles.iter().enumerate() {
+            draw_rx(f, app, port, tile);
+        }
+    } else {
+        draw_rx(f, app, app.current, chunks[1]);
+    }
 
-    // bindings
-    draw_bindings(f, chunks[2]);
+    // bindings, replaced by the query line while searching and by the progress
+    // bar while sending a file
+    if !app.mode.is_search() {
+        match app.session().sending {
+            Some(_) => draw_send_progress(f, app, chunks[2]),
+            None => draw_bindings(f, &app.keymap, chunks[2]),
+        }
+    }
 
     // status line
     draw_status(f, app, chunks[3]);
@@ -36,18 +97,117 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     match &app.mode {
         Mode::WannaQuit => draw_quit_popup(f),
         Mode::BaudInput(s) => draw_baud_popup(f, s),
+        Mode::Config(menu) => draw_config_popup(f, app, menu),
+        Mode::Macros(menu) => draw_macro_popup(f, app, menu),
+        Mode::MacroEdit(editor) => draw_macro_editor(f, editor),
+        Mode::SendFile(form) => draw_send_file_popup(f, form),
+        Mode::Transfer(form) => draw_transfer_popup(f, form),
+        Mode::Help(scroll) => draw_help_popup(f, app, *scroll),
+        Mode::PickPort(picker) | Mode::AddPort(picker) => {
+            draw_port_picker(f, picker, centered_rect(80, 60, f.size()))
+        }
+        Mode::Search(query) => draw_search_line(f, query, chunks[2]),
         _ => (),
     };
 }
 
+/// Single RX pane taking the whole screen, as in terminal mode
+fn draw_terminal<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(f.size());
+    draw_rx(f, app, app.current, chunks[0]);
+    draw_status(f, app, chunks[1]);
+}
+
+/// Names of the open ports in their timeline colors, the current one highlighted
+fn draw_port_tabs<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect) {
+    let titles = app
+        .sessions
+        .iter()
+        .enumerate()
+        .map(|(port, s)| {
+            let mut spans = vec![Span::styled(
+                format!(" {} ", s.name()),
+                Style::default().fg(port_color(port)),
+            )];
+            if s.disconnected {
+                spans.push(Span::styled("✕ ", Style::default().fg(Color::Red)));
+            }
+            Spans::from(spans)
+        })
+        .collect();
+    let tabs = Tabs::new(titles)
+        .select(app.current)
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+    f.render_widget(tabs, rect);
+}
+
+/// Color of the `port`th port in the tabs, the tiles and the timeline
+fn port_color(port: usize) -> Color {
+    const PALETTE: &[Color] = &[
+        Color::LightCyan,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightMagenta,
+        Color::LightBlue,
+        Color::LightRed,
+    ];
+    PALETTE[port % PALETTE.len()]
+}
+
+/// Style of a console title, naming and coloring its port when there are several
+fn title_style(app: &App, port: usize, focused: bool) -> Style {
+    let style = focus_style(focused);
+    if app.sessions.len() > 1 {
+        style.fg(port_color(port))
+    } else {
+        style
+    }
+}
+
+/// Opens a console title, with the port name when there are several
+fn title_start(app: &App, port: usize, name: &str) -> String {
+    if app.sessions.len() > 1 {
+        format!("[{name} {}", app.sessions[port].name())
+    } else {
+        format!("[{name}")
+    }
+}
+
 fn draw_tx<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect) {
-    let title = if app.tx.is_ascii() {
-        "[TX]"
+    let port = app.current;
+    let mut title = title_start(app, port, "TX");
+    let style = title_style(app, port, app.focus == Pane::Tx);
+    let display = &app.sessions[port].tx.display;
+    if let Some(view) = display.view_name() {
+        title.push_str(" - ");
+        title.push_str(view);
+    }
+    push_scroll_title(&mut title, display);
+    let line_mode = app.send_mode == SendMode::Line;
+    let block = Block::default()
+        .title(Span::styled(title, style))
+        .borders(Borders::all())
+        .border_type(if app.mode.is_insert() && !line_mode {
+            BorderType::Thick
+        } else {
+            BorderType::Plain
+        });
+    // in line mode the cursor lives in the editor
+    let cursor = if line_mode { ' ' } else { app.cursor() };
+    let display = &mut app.sessions[port].tx.display;
+    if display.is_dump() {
+        draw_dump(f, display, block, cursor, rect);
     } else {
-        "[TX - Hex]"
-    };
+        draw_console(f, display, block, cursor, rect, |_| Style::default());
+    }
+}
+
+fn draw_line_editor<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect) {
     let block = Block::default()
-        .title(title)
+        .title("[Line]")
         .borders(Borders::all())
         .border_type(if app.mode.is_insert() {
             BorderType::Thick
@@ -55,90 +215,651 @@ fn draw_tx<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect) {
             BorderType::Plain
         });
     let inner = block.inner(rect);
-    let tx = app.tx.with_cursor(app.cursor());
-    let txt = Paragraph::new(tx.as_ref())
-        .block(block)
-        .scroll((scroll_amount(tx.as_ref(), inner), 0))
-        .wrap(Wrap { trim: false });
+    f.render_widget(block, rect);
 
-    f.render_widget(txt, rect);
+    let chars = app
+        .editor
+        .line()
+        .chars()
+        .map(|c| if c == '\t' { '⇥' } else { c })
+        .collect::<Vec<_>>();
+    let cursor = app.editor.cursor();
+    // scroll horizontally to keep the cursor in view
+    let start = (cursor + 1).saturating_sub(inner.width as usize);
+    let mut spans = vec![Span::raw(chars[start..cursor].iter().collect::<String>())];
+    if app.mode.is_insert() {
+        let at_cursor = chars.get(cursor).copied().unwrap_or(' ');
+        let style = if app.cursor() == ' ' {
+            Style::default()
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        };
+        spans.push(Span::styled(at_cursor.to_string(), style));
+        spans.push(Span::raw(chars.iter().skip(cursor + 1).collect::<String>()));
+    } else {
+        spans.push(Span::raw(chars[cursor..].iter().collect::<String>()));
+    }
+    f.render_widget(Paragraph::new(Spans::from(spans)), inner);
 }
 
-fn draw_rx<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect) {
-    let title = if app.rx.is_ascii() {
-        "[RX]"
+/// RX of the `port`th port
+fn draw_rx<B: Backend>(f: &mut Frame<B>, app: &mut App, port: usize, rect: Rect) {
+    let mut title = title_start(app, port, "RX");
+    let style = title_style(app, port, app.focus == Pane::Rx && port == app.current);
+    let rx = &app.sessions[port].rx;
+    let display = &rx.display;
+    if let Some(view) = rx.is_ansi().then_some("ANSI").or(display.view_name()) {
+        title.push_str(" - ");
+        title.push_str(view);
+    }
+    if display.timestamps != TimestampMode::Off {
+        title.push_str(" - ");
+        title.push_str(display.timestamps.name());
+    }
+    push_scroll_title(&mut title, display);
+    let block = Block::default()
+        .title(Span::styled(title, style))
+        .borders(Borders::all());
+    // only the current port shows the cursor
+    let cursor = if port == app.current {
+        app.cursor()
     } else {
-        "[RX - Hex]"
+        ' '
     };
-    let block = Block::default().title(title).borders(Borders::all());
+    let rx = &mut app.sessions[port].rx;
+    match rx.vt.as_mut() {
+        Some(vt) => draw_vt(f, vt, &mut rx.display.scroll, block, cursor != ' ', rect),
+        None if rx.display.is_dump() => draw_dump(f, &mut rx.display, block, cursor, rect),
+        None => draw_console(f, &mut rx.display, block, cursor, rect, |_| {
+            Style::default()
+        }),
+    }
+}
+
+/// Lines of every port merged in the order they completed, colored by port
+fn draw_timeline<B: Backend>(f: &mut Frame<B>, app: &mut App, rect: Rect) {
+    let display = &app.timeline.display;
+    let mut title = String::from("[Timeline");
+    if display.timestamps != TimestampMode::Off {
+        title.push_str(" - ");
+        title.push_str(display.timestamps.name());
+    }
+    push_scroll_title(&mut title, display);
+    let block = Block::default()
+        .title(Span::styled(title, focus_style(app.focus == Pane::Rx)))
+        .borders(Borders::all());
+    let Timeline {
+        display, sources, ..
+    } = &mut app.timeline;
+    let line_style = |start| match sources.at(start) {
+        Some(port) => Style::default().fg(port_color(port)),
+        None => Style::default(),
+    };
+    draw_console(f, display, block, ' ', rect, line_style);
+}
+
+/// Renders the emulated terminal screen below its scrollback
+fn draw_vt<B: Backend>(
+    f: &mut Frame<B>,
+    vt: &mut Vt,
+    scroll: &mut Scroll,
+    block: Block,
+    show_cursor: bool,
+    rect: Rect,
+) {
     let inner = block.inner(rect);
+    f.render_widget(block, rect);
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    vt.resize(inner.height as usize, inner.width as usize);
+    scroll.height = inner.height as usize;
+    scroll.rows = vt.total_rows();
+    let top = scroll.offset();
+    let cursor = vt.cursor().filter(|_| show_cursor);
 
-    let rx = app.rx.with_cursor(app.cursor());
-    let txt = Paragraph::new(rx.as_ref())
-        .block(block)
-        .scroll((scroll_amount(rx.as_ref(), inner), 0))
-        .wrap(Wrap { trim: false });
-    f.render_widget(txt, rect);
+    let rows = (top..top + scroll.height)
+        .map_while(|r| Some((r, vt.row(r)?)))
+        .map(|(r, cells)| {
+            let mut spans = Vec::new();
+            let mut text = String::new();
+            let mut style = Style::default();
+            for (c, cell) in cells.iter().enumerate() {
+                if cell.ch == Cell::WIDE_TAIL {
+                    continue;
+                }
+                let mut cell_style = cell.style;
+                if cursor == Some((r, c)) {
+                    cell_style.add_modifier.toggle(Modifier::REVERSED);
+                }
+                if cell_style != style && !text.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut text), style));
+                }
+                style = cell_style;
+                text.push(cell.ch);
+            }
+            spans.push(Span::styled(text, style));
+            Spans::from(spans)
+        })
+        .collect::<Vec<_>>();
+    f.render_widget(Paragraph::new(rows), inner);
+}
+
+fn focus_style(focused: bool) -> Style {
+    if focused {
+        Style::default().add_modifier(Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
+/// Closes a console title with its follow/scroll and search state
+fn push_scroll_title(title: &mut String, display: &Display) {
+    use std::fmt::Write;
+    title.push(']');
+    if display.scroll.is_following() {
+        title.push_str("[follow]");
+    } else {
+        let percent =
+            (display.scroll.offset() + display.scroll.height) * 100 / display.scroll.rows.max(1);
+        write!(title, "[scroll {}%]", percent.min(100)).unwrap();
+    }
+    if let Some(search) = &display.search {
+        match search.current {
+            Some(c) if search.count > 0 => {
+                write!(title, "[/{} {}/{}]", search.query, c + 1, search.count).unwrap()
+            }
+            _ => write!(title, "[/{} {}]", search.query, search.count).unwrap(),
+        }
+    }
+}
+
+/// Renders a console as character wrapped rows, keeping its scroll state up to date.
+/// Only the visible lines are built, styled by `line_style` from the stream offset they
+/// start at.
+fn draw_console<B: Backend>(
+    f: &mut Frame<B>,
+    display: &mut Display,
+    block: Block,
+    cursor: char,
+    rect: Rect,
+    line_style: impl Fn(u64) -> Style,
+) {
+    let inner = block.inner(rect);
+    f.render_widget(block, rect);
+    let width = inner.width as usize;
+    if width == 0 {
+        return;
+    }
+    let stamp_style = Style::default().fg(Color::DarkGray);
+    let match_style = Style::default().bg(Color::Yellow).fg(Color::Black);
+    let current_style = Style::default().bg(Color::LightRed).fg(Color::Black);
+    let escape_style = Style::default().fg(Color::LightMagenta);
+
+    let mut rows = display.layout(width);
+    let last = display.lines().len() - 1;
+    // a high nibble typed in the hex view, drawn before the cursor
+    let pending = display
+        .pending_nibble()
+        .map_or(String::new(), |n| format!("{n:X}"));
+    // the cursor wraps to a row of its own after a full last line
+    let mut last_rows = display.line_wrap(last, None, width);
+    last_rows.push_str(&pending);
+    if last_rows.push(cursor) {
+        rows += 1;
+    }
+    let pattern = display.search_pattern();
+    let count = display.find(pattern.as_deref());
+
+    let mut scroll = std::mem::take(&mut display.scroll);
+    let mut search = display.search.take();
+    scroll.rows = rows;
+    scroll.height = inner.height as usize;
+    let mut current = None;
+    if let Some(search) = &mut search {
+        search.count = count;
+        if count == 0 {
+            search.current = None;
+        } else {
+            let top = display.line_at_row(scroll.offset());
+            let c = *search.current.get_or_insert_with(|| {
+                search.jump = true;
+                display.first_match_from(top) % count
+            });
+            let c = c.min(count - 1);
+            if search.jump {
+                search.jump = false;
+                scroll.show_row(display.match_row(c));
+            }
+            current = Some(c);
+        }
+    }
+
+    let top = scroll.offset();
+    let first = display.line_at_row(top);
+    let mut skip = top - display.line_row(first);
+    let mut match_idx = display.first_match_from(first);
+    let mut visible = Vec::with_capacity(scroll.height);
+    for (i, line) in display.lines().iter().enumerate().skip(first) {
+        if visible.len() >= scroll.height {
+            break;
+        }
+        let mut spans = Vec::new();
+        if let Some(stamp) = display.line_stamp(i) {
+            spans.push(Span::styled(stamp, stamp_style));
+        }
+        let mut matches = Vec::new();
+        while match_idx < count && display.nth_match(match_idx).0 == i {
+            let style = if Some(match_idx) == current {
+                current_style
+            } else {
+                match_style
+            };
+            matches.push((display.nth_match(match_idx).1, style));
+            match_idx += 1;
+        }
+        let base = line_style(display.line_start(i));
+        spans.extend(
+            styled_spans(&line.text, &matches, &line.escapes, escape_style)
+                .into_iter()
+                .map(|span| Span::styled(span.content, base.patch(span.style))),
+        );
+        if i == last {
+            spans.push(Span::raw(format!("{pending}{cursor}")));
+        }
+        let wrapped = wrap(spans, width);
+        visible.extend(wrapped.into_iter().skip(skip));
+        skip = 0;
+    }
+    visible.truncate(scroll.height);
+    f.render_widget(Paragraph::new(visible), inner);
+    display.scroll = scroll;
+    display.search = search;
+}
+
+/// Splits a line into spans styled by its search matches and, where no match covers them,
+/// its escaped bytes. Both lists are in order and non-overlapping.
+fn styled_spans<'a>(
+    line: &'a str,
+    matches: &[(Range<usize>, Style)],
+    escapes: &[Range<usize>],
+    escape_style: Style,
+) -> Vec<Span<'a>> {
+    if matches.is_empty() && escapes.is_empty() {
+        return vec![Span::raw(line)];
+    }
+    let mut bounds = matches
+        .iter()
+        .flat_map(|(m, _)| [m.start, m.end])
+        .chain(escapes.iter().flat_map(|e| [e.start, e.end]))
+        .chain([0, line.len()])
+        .collect::<Vec<_>>();
+    bounds.sort_unstable();
+    bounds.dedup();
+    let (mut m, mut e) = (0, 0);
+    bounds
+        .windows(2)
+        .map(|w| {
+            let (start, end) = (w[0], w[1]);
+            while matches.get(m).is_some_and(|(r, _)| r.end <= start) {
+                m += 1;
+            }
+            while escapes.get(e).is_some_and(|r| r.end <= start) {
+                e += 1;
+            }
+            let style = match (matches.get(m), escapes.get(e)) {
+                (Some((r, style)), _) if r.start <= start => *style,
+                (_, Some(r)) if r.start <= start => escape_style,
+                _ => Style::default(),
+            };
+            Span::styled(&line[start..end], style)
+        })
+        .collect()
+}
+
+/// Renders a console as `hexdump -C` style rows laid out for the current width.
+/// Only the visible rows are built.
+fn draw_dump<B: Backend>(
+    f: &mut Frame<B>,
+    display: &mut Display,
+    block: Block,
+    cursor: char,
+    rect: Rect,
+) {
+    let inner = block.inner(rect);
+    f.render_widget(block, rect);
+    if inner.width == 0 || inner.height == 0 {
+        return;
+    }
+    let offset_style = Style::default().fg(Color::DarkGray);
+    let match_style = Style::default().bg(Color::Yellow).fg(Color::Black);
+    let current_style = Style::default().bg(Color::LightRed).fg(Color::Black);
+
+    let per_row = display.dump_width.bytes_per_row(inner.width as usize);
+    let rows = display.dump_layout(per_row);
+    let first_row = display.dump_first_row();
+    let pattern = display.search_bytes();
+    let pending = display.pending_nibble();
+    // detached so `display` can stay borrowed by the bytes below
+    let mut scroll = std::mem::take(&mut display.scroll);
+    let mut search = display.search.take();
+    let matches = display.find_bytes(pattern.as_ref());
+    // stream offset of the first byte of a visible row
+    let row_start = |row: usize| (first_row + row as u64) * per_row as u64;
+
+    scroll.rows = rows;
+    scroll.height = inner.height as usize;
+    let mut current = None;
+    if let Some(search) = &mut search {
+        search.count = matches.len();
+        if search.count == 0 {
+            search.current = None;
+        } else {
+            let top = row_start(scroll.offset());
+            let c = *search.current.get_or_insert_with(|| {
+                search.jump = true;
+                matches.partition_point(|m| m.start < top) % matches.len()
+            });
+            let c = c.min(search.count - 1);
+            if search.jump {
+                search.jump = false;
+                let row = matches[c].start / per_row as u64 - first_row;
+                scroll.show_row(row as usize);
+            }
+            current = Some(c);
+        }
+    }
+
+    let top = scroll.offset();
+    let bottom = (top + scroll.height).min(scroll.rows);
+    let (first, last) = (row_start(top), row_start(bottom));
+    // highlight of every visible byte
+    let mut highlight = vec![None; (last - first) as usize];
+    let visible_matches = matches.partition_point(|m| m.end <= first)..;
+    for (i, m) in matches.range(visible_matches.clone()).enumerate() {
+        if m.start >= last {
+            break;
+        }
+        let style = if Some(visible_matches.start + i) == current {
+            current_style
+        } else {
+            match_style
+        };
+        for b in m.start.max(first)..m.end.min(last) {
+            highlight[(b - first) as usize] = Some(style);
+        }
+    }
+
+    let bytes = display.bytes();
+    let (base, end) = (display.base(), display.base() + bytes.len() as u64);
+    let rows = (top..bottom)
+        .map(|row| {
+            let start = row_start(row);
+            let mut spans = vec![Span::styled(format!("{start:08X}  "), offset_style)];
+            let mut gutter = vec![Span::raw(" |")];
+            for i in start..start + per_row as u64 {
+                if i > start && (i - start).is_multiple_of(8) {
+                    spans.push(Span::raw(" "));
+                }
+                if i < base {
+                    // dropped from the scrollback
+                    spans.push(Span::raw("   "));
+                    gutter.push(Span::raw(" "));
+                } else if i < end {
+                    let b = bytes[(i - base) as usize];
+                    let style = highlight[(i - first) as usize].unwrap_or_default();
+                    spans.push(Span::styled(format!("{b:02X}"), style));
+                    spans.push(Span::raw(" "));
+                    let c = if b.is_ascii_graphic() || b == b' ' {
+                        char::from(b)
+                    } else {
+                        '.'
+                    };
+                    gutter.push(Span::styled(c.to_string(), style));
+                } else if i == end {
+                    let slot = match pending {
+                        Some(n) => format!("{n:X}{cursor} "),
+                        None => format!("{cursor}  "),
+                    };
+                    spans.push(Span::raw(slot));
+                } else {
+                    spans.push(Span::raw("   "));
+                }
+            }
+            if start < end {
+                gutter.push(Span::raw("|"));
+                spans.extend(gutter);
+            }
+            Spans::from(spans)
+        })
+        .collect::<Vec<_>>();
+    f.render_widget(Paragraph::new(rows), inner);
+    display.scroll = scroll;
+    display.search = search;
+}
+
+/// Splits a line into rows of at most `width` columns
+fn wrap(spans: Vec<Span>, width: usize) -> Vec<Spans> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut line_wrap = LineWrap::new(width);
+    for span in spans {
+        let mut chunk = String::new();
+        for c in span.content.chars() {
+            if line_wrap.push(c) {
+                row.push(Span::styled(std::mem::take(&mut chunk), span.style));
+                rows.push(Spans::from(std::mem::take(&mut row)));
+            }
+            chunk.push(c);
+        }
+        if !chunk.is_empty() {
+            row.push(Span::styled(chunk, span.style));
+        }
+    }
+    rows.push(Spans::from(row));
+    rows
 }
 
 fn draw_status<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect) {
     let bold = Style::default().add_modifier(Modifier::BOLD);
-    let port_name = app.serial.name().unwrap_or_else(|| String::from("serial"));
-
-    let baud_rate = app
-        .serial
-        .baud_rate()
-        .map(|b| b.to_string())
-        .unwrap_or_else(|_| String::from("<baud>"));
-    let mode = if app.mode.is_insert() {
-        "INSERT"
-    } else {
-        "NORMAL"
+    let s = app.session();
+    let mut port_name = s.serial.name().unwrap_or_else(|| String::from("serial"));
+    if app.sessions.len() > 1 {
+        port_name = format!("[{}/{}] {port_name}", app.current + 1, app.sessions.len());
+    }
+    let framing = s.line.to_string();
+    let mode = match (&app.mode, app.send_mode) {
+        (Mode::Terminal, _) => format!("TERMINAL ({} to exit)", keys::key_name(app.escape_key)),
+        (Mode::Insert, SendMode::Char) => String::from("INSERT"),
+        (Mode::Insert, SendMode::Line) => String::from("INSERT LINE"),
+        (_, SendMode::Char) => String::from("NORMAL"),
+        (_, SendMode::Line) => String::from("NORMAL LINE"),
     };
-    let crlf = if app.tx.lf_crlf { "CR + LF" } else { "LF" };
-    let spans = Spans::from(vec![
+    let eol = format!(
+        "TX:{} RX:{}",
+        s.tx.line_ending.name(),
+        s.rx.display.newline().name()
+    );
+    let mut spans = vec![
         Span::styled(mode, bold),
         Span::raw(" | "),
         Span::styled(port_name, bold),
         Span::raw(" | "),
-        Span::styled(baud_rate, bold),
+    ];
+    if s.disconnected {
+        let status = if app.reconnect {
+            "DISCONNECTED, waiting for the port"
+        } else {
+            "DISCONNECTED"
+        };
+        spans.push(Span::styled(status, bold.fg(Color::White).bg(Color::Red)));
+        spans.push(Span::raw(" | "));
+    }
+    spans.extend([
+        Span::styled(framing, bold),
         Span::raw(" | "),
-        Span::styled(crlf, bold),
+        Span::styled(eol, bold),
+        Span::raw(" |"),
     ]);
+    let modem = &s.modem;
+    for (name, level) in [
+        ("DTR", Some(s.dtr)),
+        ("RTS", Some(s.rts)),
+        ("CTS", modem.cts),
+        ("DSR", modem.dsr),
+        ("DCD", modem.dcd),
+        ("RI", modem.ri),
+    ] {
+        spans.push(Span::raw(" "));
+        spans.push(line_level(name, level));
+    }
+    if s.break_until.is_some() {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled("BREAK", bold.fg(Color::White).bg(Color::Red)));
+    }
+    if s.logger.is_active() {
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled("● REC", bold.fg(Color::Red)));
+    }
+    if let Some(msg) = &app.message {
+        let style = if msg.is_error {
+            bold.fg(Color::Red)
+        } else {
+            bold
+        };
+        spans.push(Span::raw(" | "));
+        spans.push(Span::styled(msg.text.as_str(), style));
+    }
+    let spans = Spans::from(spans);
     let p = Paragraph::new(spans).style(Style::default().bg(Color::DarkGray));
     f.render_widget(p, rect);
 }
 
-static BINDINGS: &[(&str, &str)] = &[
-    ("q", "quit"),
-    ("H", "TX hex"),
-    ("h", "RX hex"),
-    ("C", "clear TX"),
-    ("c", "clear RX"),
-    ("l", "map LF to CR + LF"),
-    ("b", "change baud rate"),
-    ("i", "insert mode"),
-    ("ESC", "normal mode"),
-];
-fn draw_bindings<B: Backend>(f: &mut Frame<B>, rect: Rect) {
+/// Control line indicator, green when asserted and dimmed when unknown
+fn line_level(name: &str, level: Option<bool>) -> Span<'static> {
+    let (symbol, style) = match level {
+        Some(true) => ('●', Style::default().fg(Color::LightGreen)),
+        Some(false) => ('○', Style::default()),
+        None => ('?', Style::default().fg(Color::Gray)),
+    };
+    Span::styled(format!("{name}{symbol}"), style)
+}
+
+fn draw_bindings<B: Backend>(f: &mut Frame<B>, keymap: &Keymap, rect: Rect) {
+    let bindings = Action::ALL
+        .iter()
+        .filter(|a| a.in_bar())
+        .map(|&a| (keymap.key_names(a), a.description()))
+        .filter(|(keys, _)| !keys.is_empty())
+        .chain([(String::from("F1-F12"), "send macro")]);
     let spans = Spans::from(
-        BINDINGS
-            .iter()
-            .map(|(key, action)| {
+        Itertools::intersperse(
+            bindings.map(|(keys, action)| {
                 [
-                    Span::styled(*key, Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(keys, Style::default().add_modifier(Modifier::BOLD)),
                     Span::raw(": "),
-                    Span::raw(*action),
+                    Span::raw(action),
                 ]
-            })
-            .intersperse([Span::raw(" "), Span::raw("|"), Span::raw(" ")])
-            .flatten()
-            .collect::<Vec<_>>(),
+            }),
+            [Span::raw(" "), Span::raw("|"), Span::raw(" ")],
+        )
+        .flatten()
+        .collect::<Vec<_>>(),
     );
     let p = Paragraph::new(spans).style(Style::default().bg(Color::DarkGray));
     f.render_widget(p, rect);
 }
 
+/// Every NORMAL mode action with its keys
+fn draw_help_popup<B: Backend>(f: &mut Frame<B>, app: &App, scroll: u16) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default().title("Keys").borders(Borders::all());
+    let area = centered_rect(60, 80, f.size());
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    let mut rows = Action::ALL
+        .iter()
+        .map(|&a| {
+            let keys = app.keymap.key_names(a);
+            let keys = if keys.is_empty() {
+                String::from("-")
+            } else {
+                keys
+            };
+            (keys, a.description().to_string(), a.name())
+        })
+        .collect::<Vec<_>>();
+    rows.push((String::from("F1-F12"), String::from("send macro"), ""));
+    rows.push((
+        keys::key_name(app.escape_key),
+        String::from("leave terminal mode"),
+        "escape",
+    ));
+    let key_width = rows.iter().map(|(k, _, _)| k.width()).max().unwrap_or(0);
+    let desc_width = rows.iter().map(|(_, d, _)| d.width()).max().unwrap_or(0);
+    let lines = rows
+        .into_iter()
+        .map(|(keys, desc, name)| {
+            Spans::from(vec![
+                Span::styled(format!("{keys:<key_width$}  "), bold),
+                Span::raw(format!("{desc:<desc_width$}  ")),
+                Span::styled(name, Style::default().fg(Color::DarkGray)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    let max_scroll = (lines.len() as u16).saturating_sub(chunks[0].height);
+    f.render_widget(
+        Paragraph::new(lines).scroll((scroll.min(max_scroll), 0)),
+        chunks[0],
+    );
+    let help = Paragraph::new("j/k: scroll | any other key: close")
+        .style(Style::default().bg(Color::DarkGray));
+    f.render_widget(help, chunks[1]);
+}
+
+fn draw_send_progress<B: Backend>(f: &mut Frame<B>, app: &App, rect: Rect) {
+    let Some(send) = &app.session().sending else {
+        return;
+    };
+    let ratio = if send.is_empty() {
+        1.0
+    } else {
+        send.sent() as f64 / send.len() as f64
+    };
+    let mut label = format!(
+        "sending {} {}/{} bytes ({:.0}%)",
+        send.path.display(),
+        send.sent(),
+        send.len(),
+        ratio * 100.0
+    );
+    let keys = app.keymap.key_names(Action::CancelSend);
+    if !keys.is_empty() {
+        label.push_str(&format!(" | {keys}: {}", Action::CancelSend.description()));
+    }
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
+        .ratio(ratio)
+        .label(label);
+    f.render_widget(gauge, rect);
+}
+
+fn draw_search_line<B: Backend>(f: &mut Frame<B>, query: &str, rect: Rect) {
+    use ratatui::symbols::block::SEVEN_EIGHTHS as cursor;
+    let spans = Spans::from(vec![
+        Span::raw("/"),
+        Span::raw(query),
+        Span::styled(cursor, Style::default().add_modifier(Modifier::SLOW_BLINK)),
+    ]);
+    f.render_widget(Paragraph::new(spans), rect);
+}
+
 fn draw_quit_popup<B: Backend>(f: &mut Frame<B>) {
     let block = Block::default().title("Quit").borders(Borders::all());
     let area = centered_rect(30, 20, f.size());
@@ -149,40 +870,389 @@ fn draw_quit_popup<B: Backend>(f: &mut Frame<B>) {
     f.render_widget(txt, area);
 }
 
-fn draw_baud_popup<B: Backend>(f: &mut Frame<B>, baud_input: &str) {
+fn draw_baud_popup<B: Backend>(f: &mut Frame<B>, form: &BaudForm) {
     use ratatui::symbols::block::SEVEN_EIGHTHS as cursor;
     let block = Block::default().title("Baud Rate").borders(Borders::all());
     let area = centered_rect(20, 20, f.size());
-    let spans = Spans::from(vec![
+    let mut lines = vec![Spans::from(vec![
         Span::raw("Baud rate: "),
-        Span::raw(baud_input),
+        Span::raw(form.value.as_str()),
         Span::styled(cursor, Style::default().add_modifier(Modifier::SLOW_BLINK)),
-    ]);
-    let txt = Paragraph::new(spans).block(block).wrap(Wrap { trim: true });
+    ])];
+    if let Some(e) = &form.error {
+        lines.push(Spans::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let txt = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
     f.render_widget(Clear, area);
     f.render_widget(txt, area);
 }
 
-// functions for autoscrolling the text areas
-fn scroll_amount(s: &str, area: Rect) -> u16 {
-    let lines = lines(s, area);
-    if lines > area.height {
-        lines - area.height
+fn draw_config_popup<B: Backend>(f: &mut Frame<B>, app: &App, menu: &ConfigMenu) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title("Serial Settings")
+        .borders(Borders::all());
+    let area = centered_rect(40, 50, f.size());
+    let mut lines = Setting::ALL
+        .iter()
+        .enumerate()
+        .map(|(i, &setting)| {
+            let style = if i == menu.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Spans::from(vec![
+                Span::styled(format!("{:<14}", setting.name()), style),
+                Span::raw(" < "),
+                Span::styled(app.setting_value(setting), bold),
+                Span::raw(" >"),
+            ])
+        })
+        .collect::<Vec<_>>();
+    lines.push(Spans::default());
+    match &menu.error {
+        Some(e) => lines.push(Spans::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+        None => lines.push(Spans::from(Span::raw(
+            "j/k: select | h/l: change | Esc: close",
+        ))),
+    }
+    let txt = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+    f.render_widget(Clear, area);
+    f.render_widget(txt, area);
+}
+
+fn draw_macro_popup<B: Backend>(f: &mut Frame<B>, app: &App, menu: &MacroMenu) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default().title("Macros").borders(Borders::all());
+    let area = centered_rect(60, 50, f.size());
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+    if app.macros.list.is_empty() {
+        f.render_widget(
+            Paragraph::new("No macros yet, press n to add one"),
+            chunks[0],
+        );
+    } else {
+        let name_width = app
+            .macros
+            .list
+            .iter()
+            .map(|m| m.name.width())
+            .max()
+            .unwrap_or(0);
+        let items = app
+            .macros
+            .list
+            .iter()
+            .map(|m| {
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("{:<4}", m.key_name()), bold),
+                    Span::raw(format!("{:<name_width$}  ", m.name)),
+                    Span::styled(m.data.as_str(), Style::default().fg(Color::DarkGray)),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        state.select(Some(menu.selected));
+        f.render_stateful_widget(list, chunks[0], &mut state);
+    }
+    let help = Paragraph::new("Enter/F1-F12: send | n: new | e: edit | d: delete | Esc: close")
+        .style(Style::default().bg(Color::DarkGray));
+    f.render_widget(help, chunks[1]);
+}
+
+fn draw_macro_editor<B: Backend>(f: &mut Frame<B>, editor: &MacroEditor) {
+    use ratatui::symbols::block::SEVEN_EIGHTHS as cursor;
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let title = if editor.index.is_some() {
+        "Edit Macro"
     } else {
-        0
+        "New Macro"
+    };
+    let block = Block::default().title(title).borders(Borders::all());
+    let area = centered_rect(60, 40, f.size());
+    let mut lines = MacroEditor::FIELDS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let mut spans = vec![
+                Span::styled(format!("{name:<6}"), bold),
+                Span::raw(editor.value(i)),
+            ];
+            if i == editor.field {
+                spans.push(Span::styled(
+                    cursor,
+                    Style::default().add_modifier(Modifier::SLOW_BLINK),
+                ));
+            }
+            Spans::from(spans)
+        })
+        .collect::<Vec<_>>();
+    lines.push(Spans::default());
+    lines.push(Spans::from(Span::styled(
+        "Key: F1-F12 or empty. Data: text with \\r \\n \\xNN... escapes, or hex:01 02",
+        Style::default().fg(Color::DarkGray),
+    )));
+    match &editor.error {
+        Some(e) => lines.push(Spans::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+        None => lines.push(Spans::from(Span::raw(
+            "Tab: next field | Enter: save | Esc: cancel",
+        ))),
     }
+    let txt = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(txt, area);
 }
-fn lines(s: &str, area: Rect) -> u16 {
-    let mut line_count = 0;
-    for line in s.lines() {
-        line_count += 1;
-        let mut line_len = line.len();
-        while line_len > area.width as usize {
-            line_count += 1;
-            line_len -= area.width as usize;
+
+fn draw_send_file_popup<B: Backend>(f: &mut Frame<B>, form: &SendFileForm) {
+    use ratatui::symbols::block::SEVEN_EIGHTHS as cursor;
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default().title("Send File").borders(Borders::all());
+    let area = centered_rect(60, 40, f.size());
+    let mut lines = SendFileForm::FIELDS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let mut spans = vec![
+                Span::styled(format!("{name:<14}"), bold),
+                Span::raw(form.value(i)),
+            ];
+            if i == form.field {
+                spans.push(Span::styled(
+                    cursor,
+                    Style::default().add_modifier(Modifier::SLOW_BLINK),
+                ));
+            }
+            Spans::from(spans)
+        })
+        .collect::<Vec<_>>();
+    lines.push(Spans::default());
+    if !form.completions.is_empty() {
+        lines.push(Spans::from(Span::styled(
+            form.completions.join("  "),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    match &form.error {
+        Some(e) => lines.push(Spans::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+        None => lines.push(Spans::from(Span::raw(
+            "Tab: complete path | Up/Down: field | Enter: send | Esc: cancel",
+        ))),
+    }
+    let txt = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(txt, area);
+}
+
+fn draw_transfer_popup<B: Backend>(f: &mut Frame<B>, form: &TransferForm) {
+    use ratatui::symbols::block::SEVEN_EIGHTHS as cursor;
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title("File Transfer")
+        .borders(Borders::all());
+    let area = centered_rect(60, 40, f.size());
+    let selected = |i: usize| {
+        if i == form.field {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
         }
+    };
+    let mut path = vec![
+        Span::styled("Path      ", bold),
+        Span::raw(form.path.as_str()),
+    ];
+    if form.field == 2 {
+        path.push(Span::styled(
+            cursor,
+            Style::default().add_modifier(Modifier::SLOW_BLINK),
+        ));
+    }
+    let mut lines = vec![
+        Spans::from(vec![
+            Span::styled("Protocol  ", bold),
+            Span::raw("< "),
+            Span::styled(form.protocol.name(), selected(0)),
+            Span::raw(" >"),
+        ]),
+        Spans::from(vec![
+            Span::styled("Direction ", bold),
+            Span::raw("< "),
+            Span::styled(form.direction.name(), selected(1)),
+            Span::raw(" >"),
+        ]),
+        Spans::from(path),
+        Spans::from(Span::styled(
+            form.path_hint(),
+            Style::default().fg(Color::DarkGray),
+        )),
+        Spans::default(),
+    ];
+    if !form.completions.is_empty() {
+        lines.push(Spans::from(Span::styled(
+            form.completions.join("  "),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    match &form.error {
+        Some(e) => lines.push(Spans::from(Span::styled(
+            e.as_str(),
+            Style::default().fg(Color::Red),
+        ))),
+        None => lines.push(Spans::from(Span::raw(
+            "Up/Down: field | h/l: change | Tab: complete path | Enter: start | Esc: cancel",
+        ))),
+    }
+    let txt = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(txt, area);
+}
+
+/// Draws a running file transfer over the whole screen
+pub fn draw_transfer<B: Backend>(f: &mut Frame<B>, request: &Request, progress: &Progress) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let title = format!("{} {}", request.protocol.name(), request.direction.name());
+    let block = Block::default().title(title).borders(Borders::all());
+    let area = centered_rect(60, 40, f.size());
+    let inner = block.inner(area);
+    f.render_widget(Clear, f.size());
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+    let file = match progress.file.as_str() {
+        "" => "waiting for the other side...",
+        name => name,
+    };
+    let done = match request.direction {
+        transfer::Direction::Send => "Sent     ",
+        transfer::Direction::Receive => "Received ",
+    };
+    let size = match progress.total {
+        Some(total) => format!("{} / {total} bytes", progress.bytes),
+        None => format!("{} bytes", progress.bytes),
+    };
+    let lines = vec![
+        Spans::from(vec![Span::styled("File     ", bold), Span::raw(file)]),
+        Spans::from(vec![Span::styled(done, bold), Span::raw(size)]),
+        Spans::from(vec![
+            Span::styled("Done     ", bold),
+            Span::raw(format!("{} files", progress.files.len())),
+        ]),
+        Spans::from(vec![
+            Span::styled("Retries  ", bold),
+            Span::raw(progress.retries.to_string()),
+        ]),
+    ];
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+    let ratio = match progress.total {
+        Some(0) => 1.0,
+        Some(total) => (progress.bytes as f64 / total as f64).min(1.0),
+        None => 0.0,
+    };
+    let gauge = Gauge::default()
+        .gauge_style(Style::default().fg(Color::Green).bg(Color::DarkGray))
+        .ratio(ratio);
+    f.render_widget(gauge, chunks[1]);
+    f.render_widget(Paragraph::new("Esc: cancel"), chunks[2]);
+}
+
+/// Draws the port selection list with its filter line inside `area`
+pub fn draw_port_picker<B: Backend>(f: &mut Frame<B>, picker: &PortPicker, area: Rect) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title("Select Port")
+        .borders(Borders::all());
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let filter = Spans::from(vec![
+        Span::styled("Filter: ", bold),
+        Span::raw(picker.filter.as_str()),
+        Span::styled(
+            ratatui::symbols::block::SEVEN_EIGHTHS,
+            Style::default().add_modifier(Modifier::SLOW_BLINK),
+        ),
+    ]);
+    f.render_widget(Paragraph::new(filter), chunks[0]);
+
+    let ports = picker.filtered();
+    if ports.is_empty() {
+        let msg = match &picker.error {
+            Some(e) => Span::styled(e.as_str(), Style::default().fg(Color::Red)),
+            None => Span::raw("No serial ports found"),
+        };
+        f.render_widget(Paragraph::new(msg), chunks[1]);
+    } else {
+        let items = ports
+            .iter()
+            .map(|p| {
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("{:<20}", p.port_name), bold),
+                    Span::raw(" "),
+                    Span::raw(picker::describe(p)),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        state.select(Some(picker.selected));
+        f.render_stateful_widget(list, chunks[1], &mut state);
+    }
+
+    let mut help = String::from("Up/Down: select | Enter: open | Ctrl-r: rescan | Esc: cancel");
+    if let Some(clear) = picker.clear_history {
+        help.push_str(" | Ctrl-x: clear history ");
+        help.push_str(if clear { "[x]" } else { "[ ]" });
     }
-    line_count as u16
+    let help = Paragraph::new(help).style(Style::default().bg(Color::DarkGray));
+    f.render_widget(help, chunks[2]);
 }
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
@@ -0,0 +1,811 @@
+use crate::decode::{ControlStyle, Decoder, Encoding};
+use ratatui::style::{Color, Modifier, Style};
+use std::collections::VecDeque;
+use std::mem;
+use unicode_width::UnicodeWidthChar;
+
+/// One character cell of the terminal grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub style: Style,
+}
+
+impl Cell {
+    /// placeholder for the right half of a double width character
+    pub const WIDE_TAIL: char = '\0';
+
+    fn blank(style: Style) -> Self {
+        Self { ch: ' ', style }
+    }
+}
+
+/// Escape sequence parser state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// `ESC (`, `ESC )` and friends, waiting for the charset byte
+    Charset(u8),
+    /// `ESC #`, waiting for its final byte
+    Hash,
+    Csi,
+    /// OSC, DCS and other strings, ignored until BEL or ST
+    Str,
+    /// ESC seen inside a string, possibly starting ST
+    StrEscape,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    style: Style,
+    graphics: [bool; 2],
+    shifted: bool,
+}
+
+/// VT100/ANSI terminal emulator rendering received bytes into a grid of styled cells
+pub struct Vt {
+    rows: usize,
+    cols: usize,
+    grid: Vec<Vec<Cell>>,
+    /// lines scrolled off the top of the main screen, oldest first
+    scrollback: VecDeque<Vec<Cell>>,
+    /// main screen and its cursor, stashed while the alternate screen is shown
+    main: Option<(Vec<Vec<Cell>>, SavedCursor)>,
+    row: usize,
+    col: usize,
+    /// the last column was written, the next printable character wraps
+    wrap_pending: bool,
+    style: Style,
+    saved: SavedCursor,
+    /// scroll region, inclusive
+    top: usize,
+    bottom: usize,
+    autowrap: bool,
+    /// DECCKM, cursor keys send `ESC O` sequences
+    app_cursor: bool,
+    cursor_visible: bool,
+    /// DEC line drawing selected in G0 and G1
+    graphics: [bool; 2],
+    /// G1 shifted in with SO
+    shifted: bool,
+    state: State,
+    params: Vec<u16>,
+    private: Option<u8>,
+    decoder: Decoder,
+    decoded: String,
+    /// answers to device status queries, to be written back to the port
+    replies: Vec<u8>,
+}
+
+impl Vt {
+    const SCROLLBACK: usize = 10_000;
+    const TAB: usize = 8;
+
+    pub fn new(rows: usize, cols: usize, encoding: Encoding) -> Self {
+        let (rows, cols) = (rows.max(1), cols.max(1));
+        Self {
+            rows,
+            cols,
+            grid: vec![vec![Cell::blank(Style::default()); cols]; rows],
+            scrollback: VecDeque::new(),
+            main: None,
+            row: 0,
+            col: 0,
+            wrap_pending: false,
+            style: Style::default(),
+            saved: SavedCursor::default(),
+            top: 0,
+            bottom: rows - 1,
+            autowrap: true,
+            app_cursor: false,
+            cursor_visible: true,
+            graphics: [false; 2],
+            shifted: false,
+            state: State::Ground,
+            params: Vec::new(),
+            private: None,
+            decoder: Decoder::new(encoding, ControlStyle::Hex),
+            decoded: String::new(),
+            replies: Vec::new(),
+        }
+    }
+    /// Screen size as (rows, columns)
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+    /// Number of rows, scrollback included
+    pub fn total_rows(&self) -> usize {
+        self.scrollback.len() + self.rows
+    }
+    /// `row`th row counting from the oldest scrollback line
+    pub fn row(&self, row: usize) -> Option<&[Cell]> {
+        match row.checked_sub(self.scrollback.len()) {
+            Some(r) => self.grid.get(r).map(Vec::as_slice),
+            None => self.scrollback.get(row).map(Vec::as_slice),
+        }
+    }
+    /// Cursor position counting rows like `row`, `None` while hidden
+    pub fn cursor(&self) -> Option<(usize, usize)> {
+        self.cursor_visible
+            .then_some((self.scrollback.len() + self.row, self.col))
+    }
+    pub fn app_cursor(&self) -> bool {
+        self.app_cursor
+    }
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        mem::take(&mut self.replies)
+    }
+    /// Resizes the screen, pushing lines into the scrollback to keep the cursor visible
+    pub fn resize(&mut self, rows: usize, cols: usize) {
+        let (rows, cols) = (rows.max(1), cols.max(1));
+        if (rows, cols) == (self.rows, self.cols) {
+            return;
+        }
+        let blank = Cell::blank(Style::default());
+        if self.row >= rows {
+            let excess = self.row + 1 - rows;
+            let lines = self.grid.drain(..excess).collect::<Vec<_>>();
+            if self.main.is_none() {
+                lines.into_iter().for_each(|l| self.push_scrollback(l));
+            }
+            self.row -= excess;
+        }
+        self.grid.resize(rows, vec![blank; cols]);
+        for line in &mut self.grid {
+            line.resize(cols, blank);
+        }
+        if let Some((grid, saved)) = &mut self.main {
+            grid.truncate(rows);
+            grid.resize(rows, vec![blank; cols]);
+            for line in grid {
+                line.resize(cols, blank);
+            }
+            saved.row = saved.row.min(rows - 1);
+            saved.col = saved.col.min(cols - 1);
+        }
+        self.rows = rows;
+        self.cols = cols;
+        self.top = 0;
+        self.bottom = rows - 1;
+        self.row = self.row.min(rows - 1);
+        self.col = self.col.min(cols - 1);
+        self.wrap_pending = false;
+    }
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.feed_byte(b);
+        }
+    }
+
+    fn feed_byte(&mut self, b: u8) {
+        match self.state {
+            State::Str => match b {
+                0x07 => self.state = State::Ground,
+                0x1B => self.state = State::StrEscape,
+                _ => (),
+            },
+            State::StrEscape => {
+                self.state = if b == b'\\' {
+                    State::Ground
+                } else {
+                    State::Str
+                }
+            }
+            // CAN and SUB abort a sequence
+            _ if b == 0x18 || b == 0x1A => self.state = State::Ground,
+            _ if b == 0x1B => {
+                self.flush_decoder();
+                self.state = State::Escape;
+            }
+            // other C0 controls act even in the middle of a sequence
+            _ if b < 0x20 => {
+                self.flush_decoder();
+                self.control(b);
+            }
+            State::Ground => self.print_byte(b),
+            State::Escape => self.escape(b),
+            State::Charset(set) => {
+                if let Some(g) = [b'(', b')'].iter().position(|&s| s == set) {
+                    self.graphics[g] = b == b'0';
+                }
+                self.state = State::Ground;
+            }
+            State::Hash => self.state = State::Ground,
+            State::Csi => self.csi_byte(b),
+        }
+    }
+    fn control(&mut self, b: u8) {
+        match b {
+            0x08 => {
+                self.col = self.col.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            b'\t' => {
+                self.col = ((self.col / Self::TAB + 1) * Self::TAB).min(self.cols - 1);
+                self.wrap_pending = false;
+            }
+            b'\n' | 0x0B | 0x0C => self.linefeed(),
+            b'\r' => {
+                self.col = 0;
+                self.wrap_pending = false;
+            }
+            0x0E => self.shifted = true,
+            0x0F => self.shifted = false,
+            // BEL, NUL and the rest are ignored
+            _ => (),
+        }
+    }
+    fn escape(&mut self, b: u8) {
+        self.state = State::Ground;
+        match b {
+            b'[' => {
+                self.params.clear();
+                self.private = None;
+                self.state = State::Csi;
+            }
+            b']' | b'P' | b'X' | b'^' | b'_' => self.state = State::Str,
+            b'(' | b')' | b'*' | b'+' => self.state = State::Charset(b),
+            b'#' => self.state = State::Hash,
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.col = 0;
+                self.linefeed();
+            }
+            b'M' => self.reverse_index(),
+            b'c' => self.reset(),
+            _ => (),
+        }
+    }
+    fn csi_byte(&mut self, b: u8) {
+        match b {
+            b'0'..=b'9' => {
+                if self.params.is_empty() {
+                    self.params.push(0);
+                }
+                let p = self.params.last_mut().unwrap();
+                *p = p.saturating_mul(10).saturating_add(u16::from(b - b'0'));
+            }
+            b';' | b':' => {
+                if self.params.is_empty() {
+                    self.params.push(0);
+                }
+                self.params.push(0);
+            }
+            b'<'..=b'?' => self.private = Some(b),
+            // intermediate bytes
+            0x20..=0x2F => (),
+            0x40..=0x7E => {
+                self.state = State::Ground;
+                self.csi_dispatch(b);
+            }
+            _ => self.state = State::Ground,
+        }
+    }
+    /// `i`th parameter, with 0 and missing parameters replaced by `default`
+    fn param(&self, i: usize, default: u16) -> usize {
+        match self.params.get(i) {
+            Some(&p) if p != 0 => p as usize,
+            _ => default as usize,
+        }
+    }
+    fn csi_dispatch(&mut self, b: u8) {
+        self.wrap_pending = false;
+        let n = self.param(0, 1);
+        match (self.private, b) {
+            (Some(b'?'), b'h') => self.set_private_modes(true),
+            (Some(b'?'), b'l') => self.set_private_modes(false),
+            (Some(b'>'), b'c') => self.replies.extend_from_slice(b"\x1b[>0;0;0c"),
+            (Some(_), _) => (),
+            (None, b'@') => self.insert_chars(n),
+            (None, b'A') => self.row = self.row.saturating_sub(n).max(self.top_limit()),
+            (None, b'B' | b'e') => self.row = (self.row + n).min(self.bottom_limit()),
+            (None, b'C' | b'a') => self.col = (self.col + n).min(self.cols - 1),
+            (None, b'D') => self.col = self.col.saturating_sub(n),
+            (None, b'E') => {
+                self.row = (self.row + n).min(self.bottom_limit());
+                self.col = 0;
+            }
+            (None, b'F') => {
+                self.row = self.row.saturating_sub(n).max(self.top_limit());
+                self.col = 0;
+            }
+            (None, b'G' | b'`') => self.col = (n - 1).min(self.cols - 1),
+            (None, b'H' | b'f') => {
+                self.row = (n - 1).min(self.rows - 1);
+                self.col = (self.param(1, 1) - 1).min(self.cols - 1);
+            }
+            (None, b'd') => self.row = (n - 1).min(self.rows - 1),
+            (None, b'J') => self.erase_display(self.param(0, 0)),
+            (None, b'K') => self.erase_line(self.param(0, 0)),
+            (None, b'L') => self.insert_lines(n),
+            (None, b'M') => self.delete_lines(n),
+            (None, b'P') => self.delete_chars(n),
+            (None, b'X') => {
+                let end = (self.col + n).min(self.cols);
+                self.erase_cells(self.row, self.col..end);
+            }
+            (None, b'S') => self.scroll_up(n),
+            (None, b'T') => self.scroll_down(n),
+            (None, b'm') => self.sgr(),
+            (None, b'n') => match n {
+                5 => self.replies.extend_from_slice(b"\x1b[0n"),
+                6 => {
+                    let reply = format!("\x1b[{};{}R", self.row + 1, self.col + 1);
+                    self.replies.extend_from_slice(reply.as_bytes());
+                }
+                _ => (),
+            },
+            // VT100 with advanced video option
+            (None, b'c') if self.param(0, 0) == 0 => self.replies.extend_from_slice(b"\x1b[?1;2c"),
+            (None, b'r') => {
+                let top = n - 1;
+                let bottom = self.param(1, self.rows as u16).min(self.rows) - 1;
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.row = 0;
+                    self.col = 0;
+                }
+            }
+            (None, b's') => self.save_cursor(),
+            (None, b'u') => self.restore_cursor(),
+            _ => (),
+        }
+    }
+    fn set_private_modes(&mut self, on: bool) {
+        for i in 0..self.params.len() {
+            match self.params[i] {
+                1 => self.app_cursor = on,
+                7 => self.autowrap = on,
+                25 => self.cursor_visible = on,
+                47 | 1047 => self.alternate_screen(on, false),
+                1048 if on => self.save_cursor(),
+                1048 => self.restore_cursor(),
+                1049 => self.alternate_screen(on, true),
+                _ => (),
+            }
+        }
+    }
+    fn sgr(&mut self) {
+        if self.params.is_empty() {
+            self.style = Style::default();
+            return;
+        }
+        let mut i = 0;
+        while i < self.params.len() {
+            let p = self.params[i];
+            let modifiers = &mut self.style.add_modifier;
+            match p {
+                0 => self.style = Style::default(),
+                1 => modifiers.insert(Modifier::BOLD),
+                2 => modifiers.insert(Modifier::DIM),
+                3 => modifiers.insert(Modifier::ITALIC),
+                4 => modifiers.insert(Modifier::UNDERLINED),
+                5 | 6 => modifiers.insert(Modifier::SLOW_BLINK),
+                7 => modifiers.insert(Modifier::REVERSED),
+                8 => modifiers.insert(Modifier::HIDDEN),
+                9 => modifiers.insert(Modifier::CROSSED_OUT),
+                21 | 22 => modifiers.remove(Modifier::BOLD | Modifier::DIM),
+                23 => modifiers.remove(Modifier::ITALIC),
+                24 => modifiers.remove(Modifier::UNDERLINED),
+                25 => modifiers.remove(Modifier::SLOW_BLINK),
+                27 => modifiers.remove(Modifier::REVERSED),
+                28 => modifiers.remove(Modifier::HIDDEN),
+                29 => modifiers.remove(Modifier::CROSSED_OUT),
+                30..=37 => self.style.fg = Some(ansi_color(p - 30)),
+                39 => self.style.fg = None,
+                40..=47 => self.style.bg = Some(ansi_color(p - 40)),
+                49 => self.style.bg = None,
+                90..=97 => self.style.fg = Some(ansi_color(p - 90 + 8)),
+                100..=107 => self.style.bg = Some(ansi_color(p - 100 + 8)),
+                38 | 48 => {
+                    let (color, used) = extended_color(&self.params[i + 1..]);
+                    if let Some(color) = color {
+                        if p == 38 {
+                            self.style.fg = Some(color);
+                        } else {
+                            self.style.bg = Some(color);
+                        }
+                    }
+                    i += used;
+                }
+                _ => (),
+            }
+            i += 1;
+        }
+    }
+
+    fn print_byte(&mut self, b: u8) {
+        if b == 0x7F {
+            return;
+        }
+        let mut decoded = mem::take(&mut self.decoded);
+        self.decoder.push(b, &mut decoded);
+        for c in decoded.drain(..) {
+            self.print(c);
+        }
+        self.decoded = decoded;
+    }
+    fn flush_decoder(&mut self) {
+        let mut decoded = mem::take(&mut self.decoded);
+        self.decoder.flush(&mut decoded);
+        for c in decoded.drain(..) {
+            self.print(c);
+        }
+        self.decoded = decoded;
+    }
+    fn print(&mut self, c: char) {
+        let c = if self.graphics[self.shifted as usize] {
+            line_drawing(c)
+        } else {
+            c
+        };
+        let width = c.width().unwrap_or(0);
+        // combining characters are dropped
+        if width == 0 || width > self.cols {
+            return;
+        }
+        if self.wrap_pending || self.col + width > self.cols {
+            if self.autowrap {
+                self.col = 0;
+                self.linefeed();
+            } else {
+                self.col = self.cols - width;
+            }
+        }
+        self.put(self.row, self.col, c);
+        if width == 2 {
+            self.put(self.row, self.col + 1, Cell::WIDE_TAIL);
+        }
+        self.col += width;
+        if self.col >= self.cols {
+            self.col = self.cols - 1;
+            self.wrap_pending = self.autowrap;
+        }
+    }
+    /// Writes a cell, blanking the other half of any wide character it overlaps
+    fn put(&mut self, row: usize, col: usize, ch: char) {
+        let line = &mut self.grid[row];
+        if line[col].ch == Cell::WIDE_TAIL && ch != Cell::WIDE_TAIL && col > 0 {
+            line[col - 1].ch = ' ';
+        }
+        if line.get(col + 1).is_some_and(|c| c.ch == Cell::WIDE_TAIL) {
+            line[col + 1].ch = ' ';
+        }
+        line[col] = Cell {
+            ch,
+            style: self.style,
+        };
+    }
+    /// Blank cell carrying the current background, as erase operations use
+    fn erased(&self) -> Cell {
+        Cell::blank(Style {
+            bg: self.style.bg,
+            ..Style::default()
+        })
+    }
+    fn blank_line(&self) -> Vec<Cell> {
+        vec![self.erased(); self.cols]
+    }
+    /// Cursor movement stops at the scroll region once inside it
+    fn top_limit(&self) -> usize {
+        if self.row >= self.top {
+            self.top
+        } else {
+            0
+        }
+    }
+    fn bottom_limit(&self) -> usize {
+        if self.row <= self.bottom {
+            self.bottom
+        } else {
+            self.rows - 1
+        }
+    }
+    fn linefeed(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.bottom {
+            self.scroll_up(1);
+        } else if self.row + 1 < self.rows {
+            self.row += 1;
+        }
+    }
+    fn reverse_index(&mut self) {
+        self.wrap_pending = false;
+        if self.row == self.top {
+            self.scroll_down(1);
+        } else {
+            self.row = self.row.saturating_sub(1);
+        }
+    }
+    /// Scrolls the region up, saving lines leaving the top of the main screen
+    fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.bottom - self.top + 1);
+        for _ in 0..n {
+            let line = self.grid.remove(self.top);
+            if self.top == 0 && self.main.is_none() {
+                self.push_scrollback(line);
+            }
+            self.grid.insert(self.bottom, self.blank_line());
+        }
+    }
+    fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.bottom - self.top + 1);
+        for _ in 0..n {
+            self.grid.remove(self.bottom);
+            self.grid.insert(self.top, self.blank_line());
+        }
+    }
+    fn push_scrollback(&mut self, line: Vec<Cell>) {
+        if self.scrollback.len() == Self::SCROLLBACK {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(line);
+    }
+    fn insert_lines(&mut self, n: usize) {
+        if !(self.top..=self.bottom).contains(&self.row) {
+            return;
+        }
+        for _ in 0..n.min(self.bottom - self.row + 1) {
+            self.grid.remove(self.bottom);
+            self.grid.insert(self.row, self.blank_line());
+        }
+        self.col = 0;
+    }
+    fn delete_lines(&mut self, n: usize) {
+        if !(self.top..=self.bottom).contains(&self.row) {
+            return;
+        }
+        for _ in 0..n.min(self.bottom - self.row + 1) {
+            self.grid.remove(self.row);
+            self.grid.insert(self.bottom, self.blank_line());
+        }
+        self.col = 0;
+    }
+    fn insert_chars(&mut self, n: usize) {
+        let blank = self.erased();
+        let line = &mut self.grid[self.row];
+        let n = n.min(self.cols - self.col);
+        line.truncate(self.cols - n);
+        line.splice(self.col..self.col, std::iter::repeat_n(blank, n));
+    }
+    fn delete_chars(&mut self, n: usize) {
+        let blank = self.erased();
+        let line = &mut self.grid[self.row];
+        let n = n.min(self.cols - self.col);
+        line.drain(self.col..self.col + n);
+        line.resize(self.cols, blank);
+    }
+    fn erase_cells(&mut self, row: usize, cols: std::ops::Range<usize>) {
+        let blank = self.erased();
+        self.grid[row][cols].fill(blank);
+    }
+    fn erase_line(&mut self, mode: usize) {
+        match mode {
+            0 => self.erase_cells(self.row, self.col..self.cols),
+            1 => self.erase_cells(self.row, 0..self.col + 1),
+            2 => self.erase_cells(self.row, 0..self.cols),
+            _ => (),
+        }
+    }
+    fn erase_display(&mut self, mode: usize) {
+        let rows = match mode {
+            0 => {
+                self.erase_line(0);
+                self.row + 1..self.rows
+            }
+            1 => {
+                self.erase_line(1);
+                0..self.row
+            }
+            2 => 0..self.rows,
+            3 => {
+                self.scrollback.clear();
+                0..0
+            }
+            _ => 0..0,
+        };
+        for row in rows {
+            self.erase_cells(row, 0..self.cols);
+        }
+    }
+    fn save_cursor(&mut self) {
+        self.saved = SavedCursor {
+            row: self.row,
+            col: self.col,
+            style: self.style,
+            graphics: self.graphics,
+            shifted: self.shifted,
+        };
+    }
+    fn restore_cursor(&mut self) {
+        let saved = self.saved;
+        self.row = saved.row.min(self.rows - 1);
+        self.col = saved.col.min(self.cols - 1);
+        self.style = saved.style;
+        self.graphics = saved.graphics;
+        self.shifted = saved.shifted;
+        self.wrap_pending = false;
+    }
+    /// Switches to (or back from) the alternate screen used by full screen programs
+    fn alternate_screen(&mut self, on: bool, save_cursor: bool) {
+        if on && self.main.is_none() {
+            if save_cursor {
+                self.save_cursor();
+            }
+            let blank = vec![vec![Cell::blank(Style::default()); self.cols]; self.rows];
+            let main = mem::replace(&mut self.grid, blank);
+            self.main = Some((main, self.saved));
+        } else if !on {
+            if let Some((main, saved)) = self.main.take() {
+                self.grid = main;
+                if save_cursor {
+                    self.saved = saved;
+                    self.restore_cursor();
+                }
+            }
+        }
+    }
+    /// Full reset (RIS), keeping the scrollback
+    fn reset(&mut self) {
+        let scrollback = mem::take(&mut self.scrollback);
+        let replies = mem::take(&mut self.replies);
+        *self = Self::new(self.rows, self.cols, self.decoder.encoding);
+        self.scrollback = scrollback;
+        self.replies = replies;
+    }
+}
+
+/// One of the 16 standard colors, mapped to the user's terminal palette
+fn ansi_color(n: u16) -> Color {
+    const COLORS: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::Gray,
+        Color::DarkGray,
+        Color::LightRed,
+        Color::LightGreen,
+        Color::LightYellow,
+        Color::LightBlue,
+        Color::LightMagenta,
+        Color::LightCyan,
+        Color::White,
+    ];
+    COLORS[n as usize % 16]
+}
+
+/// Parses the arguments of SGR 38/48, `5;n` or `2;r;g;b`, returning the color and the
+/// number of parameters consumed
+fn extended_color(params: &[u16]) -> (Option<Color>, usize) {
+    match params {
+        [5, n, ..] if *n < 16 => (Some(ansi_color(*n)), 2),
+        [5, n, ..] => (Some(Color::Indexed(*n as u8)), 2),
+        [2, r, g, b, ..] => (Some(Color::Rgb(*r as u8, *g as u8, *b as u8)), 4),
+        [5] | [2, ..] => (None, params.len()),
+        _ => (None, 0),
+    }
+}
+
+/// DEC special graphics, selected with `ESC ( 0`
+fn line_drawing(c: char) -> char {
+    match c {
+        '`' => '◆',
+        'a' => '▒',
+        'f' => '°',
+        'g' => '±',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(vt: &Vt, row: usize) -> String {
+        let cells = vt.row(row).unwrap();
+        cells
+            .iter()
+            .map(|c| c.ch)
+            .collect::<String>()
+            .trim_end()
+            .to_owned()
+    }
+
+    #[test]
+    fn cursor_movement_is_clamped() {
+        let mut vt = Vt::new(5, 10, Encoding::Utf8);
+        vt.feed(b"\x1b[3;4H");
+        assert_eq!(vt.cursor(), Some((2, 3)));
+        vt.feed(b"\x1b[20;40H");
+        assert_eq!(vt.cursor(), Some((4, 9)));
+        vt.feed(b"\x1b[10A\x1b[3D");
+        assert_eq!(vt.cursor(), Some((0, 6)));
+        vt.feed(b"\x1b[20D\x1b[2B\x1b[C");
+        assert_eq!(vt.cursor(), Some((2, 1)));
+        vt.feed(b"\x1b[?25l");
+        assert_eq!(vt.cursor(), None);
+    }
+
+    #[test]
+    fn scroll_region_keeps_outer_lines() {
+        let mut vt = Vt::new(5, 10, Encoding::Utf8);
+        vt.feed(b"top\x1b[5;1Hend\x1b[2;4r");
+        assert_eq!(vt.cursor(), Some((0, 0)));
+        vt.feed(b"\x1b[2Ha\r\nb\r\nc\r\nd");
+        let rows = (0..vt.total_rows())
+            .map(|r| text(&vt, r))
+            .collect::<Vec<_>>();
+        // the line scrolled out of the region is not kept in the scrollback
+        assert_eq!(rows, ["top", "b", "c", "d", "end"]);
+        vt.feed(b"\x1b[10B");
+        assert_eq!(vt.cursor(), Some((3, 1)));
+    }
+
+    #[test]
+    fn extended_colors() {
+        let mut vt = Vt::new(1, 10, Encoding::Utf8);
+        vt.feed(b"\x1b[38;5;196;48;2;1;2;3mx\x1b[38;5;1my\x1b[0mz");
+        let cells = vt.row(0).unwrap();
+        assert_eq!(cells[0].style.fg, Some(Color::Indexed(196)));
+        assert_eq!(cells[0].style.bg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(cells[1].style.fg, Some(Color::Red));
+        assert_eq!(cells[1].style.bg, Some(Color::Rgb(1, 2, 3)));
+        assert_eq!(cells[2].style, Style::default());
+    }
+
+    #[test]
+    fn wide_characters_wrap_whole() {
+        let mut vt = Vt::new(2, 5, Encoding::Utf8);
+        vt.feed("abcd世".as_bytes());
+        assert_eq!(text(&vt, 0), "abcd");
+        let chars = vt.row(1).unwrap().iter().map(|c| c.ch).collect::<Vec<_>>();
+        assert_eq!(chars, ['世', Cell::WIDE_TAIL, ' ', ' ', ' ']);
+        assert_eq!(vt.cursor(), Some((1, 2)));
+        // overwriting the right half blanks the left one
+        vt.feed(b"\x1b[2;2Hx");
+        assert_eq!(text(&vt, 1), " x");
+    }
+
+    #[test]
+    fn alternate_screen_restores_main() {
+        let mut vt = Vt::new(3, 10, Encoding::Utf8);
+        vt.feed(b"main\x1b[?1049h");
+        assert_eq!(text(&vt, 0), "");
+        vt.feed(b"alt\n\n\n\n");
+        assert_eq!(vt.total_rows(), 3);
+        vt.feed(b"\x1b[?1049l");
+        assert_eq!(text(&vt, 0), "main");
+        assert_eq!(vt.cursor(), Some((0, 4)));
+    }
+}
@@ -0,0 +1,316 @@
+//! XMODEM (checksum, CRC and 1K) and YMODEM batch transfers
+
+use crate::transfer::{self, Error, Link, Protocol, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SOH: u8 = 0x01;
+const STX: u8 = 0x02;
+const EOT: u8 = 0x04;
+const ACK: u8 = 0x06;
+const NAK: u8 = 0x15;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1A;
+/// asks for CRC-16 instead of the checksum
+const CRC: u8 = b'C';
+
+const MAX_RETRIES: usize = 10;
+/// start requests a receiver sends before giving up on the sender
+const START_POLLS: usize = 20;
+/// how long a sender waits for the receiver to start
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+const BYTE_TIMEOUT: Duration = Duration::from_secs(1);
+/// interval between the receiver's start requests
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    Sum,
+    Crc,
+}
+
+impl Check {
+    fn append(self, data: &[u8], out: &mut Vec<u8>) {
+        match self {
+            Self::Sum => out.push(data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))),
+            Self::Crc => out.extend(transfer::crc16(0, data).to_be_bytes()),
+        }
+    }
+    fn len(self) -> usize {
+        match self {
+            Self::Sum => 1,
+            Self::Crc => 2,
+        }
+    }
+}
+
+pub fn send(link: &mut Link, protocol: Protocol, paths: &[PathBuf]) -> Result<()> {
+    if protocol != Protocol::Ymodem {
+        let path = &paths[0];
+        let data = fs::read(path)?;
+        link.progress
+            .start_file(&transfer::file_name(path), Some(data.len() as u64));
+        let check = wait_start(link)?;
+        let one_k = protocol == Protocol::Xmodem1k && check == Check::Crc;
+        send_data(link, &data, check, one_k)?;
+        send_eot(link)?;
+        link.progress.end_file();
+        return Ok(());
+    }
+    for path in paths {
+        let data = fs::read(path)?;
+        let name = transfer::file_name(path);
+        link.progress.start_file(&name, Some(data.len() as u64));
+        // block 0: name, size and octal modification time
+        let mut header = name.into_bytes();
+        header.push(0);
+        header.extend(format!("{} {:o}", data.len(), transfer::mtime(path)).into_bytes());
+        header.push(0);
+        let check = wait_start(link)?;
+        send_block(link, 0, &header, check)?;
+        let check = wait_start(link)?;
+        send_data(link, &data, check, check == Check::Crc)?;
+        send_eot(link)?;
+        link.progress.end_file();
+    }
+    // an empty block 0 ends the batch
+    let check = wait_start(link)?;
+    send_block(link, 0, &[], check)
+}
+
+pub fn receive(link: &mut Link, protocol: Protocol, path: &Path) -> Result<()> {
+    let check = match protocol {
+        Protocol::Xmodem => Check::Sum,
+        _ => Check::Crc,
+    };
+    if protocol != Protocol::Ymodem {
+        let mut file = File::create(path)?;
+        link.progress.start_file(&transfer::file_name(path), None);
+        receive_data(link, check, &mut file, None, 1)?;
+        link.progress.end_file();
+        return Ok(());
+    }
+    loop {
+        let header = receive_header(link)?;
+        let mut fields = header.split(|&b| b == 0);
+        let name = String::from_utf8_lossy(fields.next().unwrap_or_default()).into_owned();
+        if name.is_empty() {
+            return Ok(());
+        }
+        let size = fields.next().and_then(|info| {
+            String::from_utf8_lossy(info)
+                .split(' ')
+                .next()?
+                .parse()
+                .ok()
+        });
+        let path = transfer::receive_path(path, &name);
+        let mut file = File::create(&path)?;
+        link.progress.start_file(&transfer::file_name(&path), size);
+        receive_data(link, check, &mut file, size, 1)?;
+        link.progress.end_file();
+    }
+}
+
+/// Waits for the receiver to ask for the first block, returning the check it wants
+fn wait_start(link: &mut Link) -> Result<Check> {
+    let mut cans = 0;
+    loop {
+        match link.read(START_TIMEOUT)? {
+            Some(CRC) => return Ok(Check::Crc),
+            Some(NAK) => return Ok(Check::Sum),
+            Some(CAN) if cans > 0 => return Err(Error::RemoteCancelled),
+            Some(CAN) => cans += 1,
+            Some(_) => cans = 0,
+            None => return Err(Error::Timeout("waiting for the receiver")),
+        }
+    }
+}
+
+/// Sends `data` as blocks numbered from 1, in 1K blocks when `one_k` while more than
+/// 128 bytes are left
+fn send_data(link: &mut Link, data: &[u8], check: Check, one_k: bool) -> Result<()> {
+    let mut pos = 0;
+    let mut num = 1u8;
+    while pos < data.len() {
+        let size = if one_k && data.len() - pos > 128 {
+            1024
+        } else {
+            128
+        };
+        let end = (pos + size).min(data.len());
+        send_block(link, num, &data[pos..end], check)?;
+        pos = end;
+        num = num.wrapping_add(1);
+        link.progress.bytes = pos as u64;
+    }
+    Ok(())
+}
+
+/// Sends a block, padded with SUB, until the receiver acknowledges it
+fn send_block(link: &mut Link, num: u8, data: &[u8], check: Check) -> Result<()> {
+    let size = if data.len() > 128 { 1024 } else { 128 };
+    let mut block = vec![if size == 1024 { STX } else { SOH }, num, !num];
+    block.extend(data);
+    // block 0 is padded with NULs
+    let pad = if num == 0 { 0 } else { SUB };
+    block.resize(3 + size, pad);
+    let payload = block[3..].to_vec();
+    check.append(&payload, &mut block);
+    for _ in 0..MAX_RETRIES {
+        link.write(&block)?;
+        if wait_ack(link)? {
+            return Ok(());
+        }
+        link.progress.retries += 1;
+    }
+    Err(Error::Protocol(format!(
+        "block {num} rejected {MAX_RETRIES} times"
+    )))
+}
+
+fn send_eot(link: &mut Link) -> Result<()> {
+    // receivers commonly NAK the first EOT to make sure it is not line noise
+    for _ in 0..MAX_RETRIES {
+        link.write(&[EOT])?;
+        if wait_ack(link)? {
+            return Ok(());
+        }
+    }
+    Err(Error::Timeout("waiting for the end of transfer ACK"))
+}
+
+/// Whether the receiver acknowledged the last block, `false` if it asked for it again
+fn wait_ack(link: &mut Link) -> Result<bool> {
+    let mut cans = 0;
+    loop {
+        match link.read(ACK_TIMEOUT)? {
+            Some(ACK) => return Ok(true),
+            Some(NAK) | None => return Ok(false),
+            Some(CAN) if cans > 0 => return Err(Error::RemoteCancelled),
+            Some(CAN) => cans += 1,
+            // a late 'C' from the receiver, or noise
+            Some(_) => cans = 0,
+        }
+    }
+}
+
+enum Packet {
+    Block(u8, Vec<u8>),
+    Eot,
+    /// nothing, or a corrupted block
+    Bad,
+}
+
+/// Reads the next block from the sender
+fn receive_packet(link: &mut Link, check: Check, timeout: Duration) -> Result<Packet> {
+    let size = match link.read(timeout)? {
+        Some(SOH) => 128,
+        Some(STX) => 1024,
+        Some(EOT) => return Ok(Packet::Eot),
+        Some(CAN) => match link.read(BYTE_TIMEOUT)? {
+            Some(CAN) => return Err(Error::RemoteCancelled),
+            _ => return Ok(Packet::Bad),
+        },
+        _ => return Ok(Packet::Bad),
+    };
+    let len = 2 + size + check.len();
+    let mut block = Vec::with_capacity(len);
+    for _ in 0..len {
+        match link.read(BYTE_TIMEOUT)? {
+            Some(b) => block.push(b),
+            None => return Ok(Packet::Bad),
+        }
+    }
+    let (num, inv) = (block[0], block[1]);
+    let data = &block[2..2 + size];
+    let mut expected = Vec::new();
+    check.append(data, &mut expected);
+    if num != !inv || block[2 + size..] != expected[..] {
+        return Ok(Packet::Bad);
+    }
+    Ok(Packet::Block(num, data.to_vec()))
+}
+
+/// Receives the YMODEM block 0
+fn receive_header(link: &mut Link) -> Result<Vec<u8>> {
+    for _ in 0..START_POLLS {
+        link.write(&[CRC])?;
+        match receive_packet(link, Check::Crc, POLL_INTERVAL)? {
+            Packet::Block(0, data) => {
+                link.write(&[ACK])?;
+                return Ok(data);
+            }
+            _ => link.purge(BYTE_TIMEOUT)?,
+        }
+    }
+    Err(Error::Timeout("waiting for the sender"))
+}
+
+/// Receives blocks numbered from `num` into `out` until EOT. `size`, when known, drops the
+/// padding of the last block.
+fn receive_data(
+    link: &mut Link,
+    check: Check,
+    out: &mut impl Write,
+    size: Option<u64>,
+    mut num: u8,
+) -> Result<()> {
+    let mut started = false;
+    let mut errors = 0;
+    let mut written = 0u64;
+    link.write(&[start_byte(check)])?;
+    loop {
+        let timeout = if started { ACK_TIMEOUT } else { POLL_INTERVAL };
+        match receive_packet(link, check, timeout)? {
+            Packet::Block(n, data) if n == num => {
+                started = true;
+                errors = 0;
+                let len = match size {
+                    Some(size) => (size.saturating_sub(written) as usize).min(data.len()),
+                    None => data.len(),
+                };
+                out.write_all(&data[..len])?;
+                written += len as u64;
+                link.progress.bytes = written;
+                num = num.wrapping_add(1);
+                link.write(&[ACK])?;
+            }
+            // our ACK got lost
+            Packet::Block(n, _) if n == num.wrapping_sub(1) => link.write(&[ACK])?,
+            Packet::Block(n, _) => {
+                return Err(Error::Protocol(format!(
+                    "block {n} out of sequence, expected {num}"
+                )))
+            }
+            Packet::Eot => {
+                link.write(&[ACK])?;
+                out.flush()?;
+                return Ok(());
+            }
+            Packet::Bad => {
+                errors += 1;
+                if errors > if started { MAX_RETRIES } else { START_POLLS } {
+                    return Err(Error::Timeout("waiting for a valid block"));
+                }
+                link.purge(BYTE_TIMEOUT)?;
+                if started {
+                    link.progress.retries += 1;
+                    link.write(&[NAK])?;
+                } else {
+                    link.write(&[start_byte(check)])?;
+                }
+            }
+        }
+    }
+}
+
+fn start_byte(check: Check) -> u8 {
+    match check {
+        Check::Sum => NAK,
+        Check::Crc => CRC,
+    }
+}
@@ -0,0 +1,606 @@
+//! ZMODEM batch transfers, streaming with CRC-16 or CRC-32 and resuming on errors
+
+use crate::transfer::{self, Error, Link, Result};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const ZPAD: u8 = b'*';
+const ZDLE: u8 = 0x18;
+const ZBIN: u8 = b'A';
+const ZHEX: u8 = b'B';
+const ZBIN32: u8 = b'C';
+const XON: u8 = 0x11;
+
+// frame types
+const ZRQINIT: u8 = 0;
+const ZRINIT: u8 = 1;
+const ZSINIT: u8 = 2;
+const ZACK: u8 = 3;
+const ZFILE: u8 = 4;
+const ZSKIP: u8 = 5;
+const ZNAK: u8 = 6;
+const ZABORT: u8 = 7;
+const ZFIN: u8 = 8;
+const ZRPOS: u8 = 9;
+const ZDATA: u8 = 10;
+const ZEOF: u8 = 11;
+const ZFERR: u8 = 12;
+const ZCHALLENGE: u8 = 14;
+const ZCAN: u8 = 16;
+
+// ends of data subpackets
+/// end of frame, header follows
+const ZCRCE: u8 = b'h';
+/// more data follows
+const ZCRCG: u8 = b'i';
+/// more data follows, ZACK expected
+const ZCRCQ: u8 = b'j';
+/// end of frame, ZACK expected
+const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// ZRINIT capabilities
+const CANFDX: u8 = 0x01;
+const CANOVIO: u8 = 0x02;
+const CANFC32: u8 = 0x20;
+const ESCCTL: u8 = 0x40;
+/// binary file, in the ZFILE flags
+const ZCBIN: u8 = 1;
+
+const MAX_RETRIES: usize = 10;
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const BYTE_TIMEOUT: Duration = Duration::from_secs(1);
+const SUBPACKET_SIZE: usize = 1024;
+/// largest subpacket accepted, as sent by ZMODEM-8K senders
+const MAX_SUBPACKET: usize = 8192;
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    kind: u8,
+    /// position, little endian, or flags with ZF0 last
+    data: [u8; 4],
+}
+
+impl Header {
+    fn new(kind: u8, data: [u8; 4]) -> Self {
+        Self { kind, data }
+    }
+    fn pos(kind: u8, pos: u64) -> Self {
+        Self::new(kind, (pos as u32).to_le_bytes())
+    }
+    fn position(&self) -> u64 {
+        u32::from_le_bytes(self.data) as u64
+    }
+    fn bytes(&self) -> [u8; 5] {
+        let [a, b, c, d] = self.data;
+        [self.kind, a, b, c, d]
+    }
+}
+
+/// Escaped byte stream of a ZMODEM session
+struct Session<'l, 'a> {
+    link: &'l mut Link<'a>,
+    /// send CRC-32 binary headers and subpackets
+    crc32: bool,
+    /// escape every control character, as asked by the receiver
+    escape_ctl: bool,
+    /// CRC of the subpackets following the last received header
+    rx_crc32: bool,
+}
+
+enum Unit {
+    Byte(u8),
+    /// end of a data subpacket
+    End(u8),
+}
+
+impl<'l, 'a> Session<'l, 'a> {
+    fn new(link: &'l mut Link<'a>) -> Self {
+        Self {
+            link,
+            crc32: false,
+            escape_ctl: false,
+            rx_crc32: false,
+        }
+    }
+
+    fn escape(&self, b: u8, out: &mut Vec<u8>) {
+        match b {
+            ZDLE | 0x10 | 0x90 | 0x11 | 0x91 | 0x13 | 0x93 => out.extend([ZDLE, b ^ 0x40]),
+            _ if self.escape_ctl && b & 0x60 == 0 => out.extend([ZDLE, b ^ 0x40]),
+            _ => out.push(b),
+        }
+    }
+    fn send_hex_header(&mut self, header: Header) -> Result<()> {
+        let bytes = header.bytes();
+        let crc = transfer::crc16(0, &bytes);
+        let mut out = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for b in bytes.into_iter().chain(crc.to_be_bytes()) {
+            out.extend(format!("{b:02x}").into_bytes());
+        }
+        out.extend([b'\r', b'\n' | 0x80]);
+        if header.kind != ZFIN && header.kind != ZACK {
+            out.push(XON);
+        }
+        self.link.write(&out)
+    }
+    fn send_binary_header(&mut self, header: Header) -> Result<()> {
+        let bytes = header.bytes();
+        let mut out = vec![ZPAD, ZDLE, if self.crc32 { ZBIN32 } else { ZBIN }];
+        let mut raw = bytes.to_vec();
+        if self.crc32 {
+            raw.extend((!transfer::crc32(!0, &bytes)).to_le_bytes());
+        } else {
+            raw.extend(transfer::crc16(0, &bytes).to_be_bytes());
+        }
+        for b in raw {
+            self.escape(b, &mut out);
+        }
+        self.link.write(&out)
+    }
+    fn send_subpacket(&mut self, data: &[u8], end: u8) -> Result<()> {
+        let mut out = Vec::with_capacity(data.len() * 2 + 16);
+        for &b in data {
+            self.escape(b, &mut out);
+        }
+        out.extend([ZDLE, end]);
+        let crc = if self.crc32 {
+            let crc = transfer::crc32(transfer::crc32(!0, data), &[end]);
+            (!crc).to_le_bytes().to_vec()
+        } else {
+            transfer::crc16(transfer::crc16(0, data), &[end])
+                .to_be_bytes()
+                .to_vec()
+        };
+        for b in crc {
+            self.escape(b, &mut out);
+        }
+        if end == ZCRCW {
+            out.push(XON);
+        }
+        self.link.write(&out)
+    }
+
+    fn read_raw(&mut self) -> Result<u8> {
+        match self.link.read(BYTE_TIMEOUT)? {
+            Some(b) => Ok(b),
+            None => Err(Error::Timeout("in the middle of a frame")),
+        }
+    }
+    /// Next unescaped byte, dropping flow control characters
+    fn read_unit(&mut self) -> Result<Unit> {
+        loop {
+            match self.read_raw()? {
+                ZDLE => break,
+                XON | 0x13 | 0x91 | 0x93 => (),
+                b => return Ok(Unit::Byte(b)),
+            }
+        }
+        // ZDLE is CAN, five of them in a row abort the session
+        let mut cans = 1;
+        loop {
+            match self.read_raw()? {
+                ZDLE => {
+                    cans += 1;
+                    if cans >= 5 {
+                        return Err(Error::RemoteCancelled);
+                    }
+                }
+                XON | 0x13 | 0x91 | 0x93 => (),
+                b @ (ZCRCE | ZCRCG | ZCRCQ | ZCRCW) => return Ok(Unit::End(b)),
+                ZRUB0 => return Ok(Unit::Byte(0x7F)),
+                ZRUB1 => return Ok(Unit::Byte(0xFF)),
+                b if b & 0x60 == 0x40 => return Ok(Unit::Byte(b ^ 0x40)),
+                b => return Err(Error::Protocol(format!("bad escape {b:#04x}"))),
+            }
+        }
+    }
+    fn read_byte(&mut self) -> Result<u8> {
+        match self.read_unit()? {
+            Unit::Byte(b) => Ok(b),
+            Unit::End(_) => Err(Error::Protocol(String::from("unexpected end of subpacket"))),
+        }
+    }
+    fn read_hex_byte(&mut self) -> Result<Option<u8>> {
+        let hi = self.read_raw()?;
+        let lo = self.read_raw()?;
+        let digits = [hi, lo];
+        Ok(std::str::from_utf8(&digits)
+            .ok()
+            .and_then(|s| u8::from_str_radix(s, 16).ok()))
+    }
+
+    /// Next header from the other side, `None` on timeout or a corrupted header
+    fn read_header(&mut self) -> Result<Option<Header>> {
+        self.read_header_within(HEADER_TIMEOUT)
+    }
+    /// Like `read_header`, giving up if no header starts within `timeout`
+    fn read_header_within(&mut self, timeout: Duration) -> Result<Option<Header>> {
+        let mut garbage = 0;
+        let mut cans = 0;
+        loop {
+            let Some(b) = self.link.read(timeout)? else {
+                return Ok(None);
+            };
+            match b {
+                ZPAD => (),
+                ZDLE => {
+                    cans += 1;
+                    if cans >= 5 {
+                        return Err(Error::RemoteCancelled);
+                    }
+                    continue;
+                }
+                _ => {
+                    cans = 0;
+                    garbage += 1;
+                    if garbage > 2 * MAX_SUBPACKET {
+                        return Ok(None);
+                    }
+                    continue;
+                }
+            }
+            cans = 0;
+            // ZPAD+ ZDLE format
+            // a timeout from here on is a header cut short, as in the header itself
+            let mut b = self.read_raw();
+            while let Ok(ZPAD) = b {
+                b = self.read_raw();
+            }
+            let res = match b {
+                Ok(ZDLE) => match self.read_raw() {
+                    Ok(ZHEX) => self.read_hex_header(),
+                    Ok(ZBIN) => self.read_binary_header(false),
+                    Ok(ZBIN32) => self.read_binary_header(true),
+                    Ok(_) => continue,
+                    Err(e) => Err(e),
+                },
+                Ok(_) => continue,
+                Err(e) => Err(e),
+            };
+            return match res {
+                Ok(h) => Ok(h),
+                Err(Error::Timeout(_) | Error::Protocol(_)) => Ok(None),
+                Err(e) => Err(e),
+            };
+        }
+    }
+    fn read_hex_header(&mut self) -> Result<Option<Header>> {
+        let mut bytes = [0; 7];
+        for b in &mut bytes {
+            match self.read_hex_byte()? {
+                Some(v) => *b = v,
+                None => return Ok(None),
+            }
+        }
+        // CR LF, and XON
+        let _ = self.link.read(Duration::from_millis(100))?;
+        let _ = self.link.read(Duration::from_millis(100))?;
+        if transfer::crc16(0, &bytes[..5]) != u16::from_be_bytes([bytes[5], bytes[6]]) {
+            return Ok(None);
+        }
+        self.rx_crc32 = false;
+        Ok(Some(Header::new(
+            bytes[0],
+            [bytes[1], bytes[2], bytes[3], bytes[4]],
+        )))
+    }
+    fn read_binary_header(&mut self, crc32: bool) -> Result<Option<Header>> {
+        let mut bytes = [0; 9];
+        let len = if crc32 { 9 } else { 7 };
+        for b in &mut bytes[..len] {
+            *b = self.read_byte()?;
+        }
+        let valid = if crc32 {
+            !transfer::crc32(!0, &bytes[..5])
+                == u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]])
+        } else {
+            transfer::crc16(0, &bytes[..5]) == u16::from_be_bytes([bytes[5], bytes[6]])
+        };
+        if !valid {
+            return Ok(None);
+        }
+        self.rx_crc32 = crc32;
+        Ok(Some(Header::new(
+            bytes[0],
+            [bytes[1], bytes[2], bytes[3], bytes[4]],
+        )))
+    }
+    /// Next data subpacket with how it ended, `None` if it is corrupted
+    fn read_subpacket(&mut self) -> Result<Option<(Vec<u8>, u8)>> {
+        let mut data = Vec::new();
+        let end = loop {
+            match self.read_unit() {
+                Ok(Unit::Byte(b)) if data.len() < MAX_SUBPACKET => data.push(b),
+                Ok(Unit::Byte(_)) => return Ok(None),
+                Ok(Unit::End(end)) => break end,
+                Err(Error::Timeout(_) | Error::Protocol(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        };
+        let crc_len = if self.rx_crc32 { 4 } else { 2 };
+        let mut crc = [0; 4];
+        for b in &mut crc[..crc_len] {
+            match self.read_byte() {
+                Ok(v) => *b = v,
+                Err(Error::Timeout(_) | Error::Protocol(_)) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+        let valid = if self.rx_crc32 {
+            !transfer::crc32(transfer::crc32(!0, &data), &[end]) == u32::from_le_bytes(crc)
+        } else {
+            transfer::crc16(transfer::crc16(0, &data), &[end])
+                == u16::from_be_bytes([crc[0], crc[1]])
+        };
+        Ok(valid.then_some((data, end)))
+    }
+    /// Asks for the data again from `pos`, the rest of the frame is skipped while
+    /// waiting for the next header
+    fn resume(&mut self, pos: u64) -> Result<()> {
+        self.link.progress.retries += 1;
+        self.send_hex_header(Header::pos(ZRPOS, pos))
+    }
+}
+
+pub fn send(link: &mut Link, paths: &[PathBuf]) -> Result<()> {
+    let mut s = Session::new(link);
+    // starts `rz` on the other side if it is a shell
+    s.link.write(b"rz\r")?;
+    s.send_hex_header(Header::pos(ZRQINIT, 0))?;
+    let mut tries = 0;
+    let flags = loop {
+        match s.read_header()? {
+            Some(h) if h.kind == ZRINIT => break h.data[3],
+            Some(h) if h.kind == ZCHALLENGE => s.send_hex_header(Header::new(ZACK, h.data))?,
+            Some(h) if h.kind == ZCAN || h.kind == ZABORT => return Err(Error::RemoteCancelled),
+            _ => {
+                tries += 1;
+                if tries > MAX_RETRIES {
+                    return Err(Error::Timeout("waiting for the receiver"));
+                }
+                s.send_hex_header(Header::pos(ZRQINIT, 0))?;
+            }
+        }
+    };
+    s.crc32 = flags & CANFC32 != 0;
+    s.escape_ctl = flags & ESCCTL != 0;
+
+    let sizes = paths
+        .iter()
+        .map(|p| fs::metadata(p).map(|m| m.len()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    for (i, path) in paths.iter().enumerate() {
+        let data = fs::read(path)?;
+        let name = transfer::file_name(path);
+        s.link.progress.start_file(&name, Some(data.len() as u64));
+        // name, then size, mtime, mode, serial number, files and bytes left
+        let mut info = name.into_bytes();
+        info.push(0);
+        let left = sizes[i..].iter().sum::<u64>();
+        info.extend(
+            format!(
+                "{} {:o} 100644 0 {} {left}",
+                data.len(),
+                transfer::mtime(path),
+                paths.len() - i
+            )
+            .into_bytes(),
+        );
+        info.push(0);
+        if send_file(&mut s, &info, &data)? {
+            s.link.progress.end_file();
+        }
+    }
+
+    for _ in 0..MAX_RETRIES {
+        s.send_hex_header(Header::pos(ZFIN, 0))?;
+        if let Some(h) = s.read_header()? {
+            if h.kind == ZFIN {
+                s.link.write(b"OO")?;
+                return Ok(());
+            }
+        }
+    }
+    Err(Error::Timeout("waiting for the end of session"))
+}
+
+/// Sends a file, `false` if the receiver skipped it
+fn send_file(s: &mut Session, info: &[u8], data: &[u8]) -> Result<bool> {
+    let mut tries = 0;
+    let mut pos = loop {
+        s.send_binary_header(Header::new(ZFILE, [0, 0, 0, ZCBIN]))?;
+        s.send_subpacket(info, ZCRCW)?;
+        match s.read_header()? {
+            Some(h) if h.kind == ZRPOS => break h.position(),
+            Some(h) if h.kind == ZSKIP => return Ok(false),
+            Some(h) if h.kind == ZCAN || h.kind == ZABORT => return Err(Error::RemoteCancelled),
+            Some(h) if h.kind == ZFERR => {
+                return Err(Error::Protocol(String::from(
+                    "the receiver can't write the file",
+                )))
+            }
+            _ => {
+                tries += 1;
+                if tries > MAX_RETRIES {
+                    return Err(Error::Timeout(
+                        "waiting for the receiver to accept the file",
+                    ));
+                }
+            }
+        }
+    };
+    let len = data.len() as u64;
+    let mut tries = 0;
+    'frame: loop {
+        pos = pos.min(len);
+        if pos < len {
+            s.send_binary_header(Header::pos(ZDATA, pos))?;
+        }
+        while pos < len {
+            let end = (pos as usize + SUBPACKET_SIZE).min(data.len());
+            let last = end == data.len();
+            s.send_subpacket(&data[pos as usize..end], if last { ZCRCE } else { ZCRCG })?;
+            pos = end as u64;
+            s.link.progress.bytes = pos;
+            // the receiver only talks while streaming to ask for data again
+            if s.link.has_input()? {
+                if let Some(h) = s.read_header_within(Duration::ZERO)? {
+                    match h.kind {
+                        ZRPOS => {
+                            s.link.progress.retries += 1;
+                            pos = h.position();
+                            continue 'frame;
+                        }
+                        ZCAN | ZABORT => return Err(Error::RemoteCancelled),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        s.send_binary_header(Header::pos(ZEOF, len))?;
+        loop {
+            match s.read_header()? {
+                Some(h) if h.kind == ZRINIT => return Ok(true),
+                Some(h) if h.kind == ZSKIP => return Ok(false),
+                Some(h) if h.kind == ZRPOS => {
+                    s.link.progress.retries += 1;
+                    pos = h.position();
+                    continue 'frame;
+                }
+                Some(h) if h.kind == ZCAN || h.kind == ZABORT => {
+                    return Err(Error::RemoteCancelled)
+                }
+                // a ZACK to a late ZCRCW or ZCRCQ
+                Some(h) if h.kind == ZACK => (),
+                _ => {
+                    tries += 1;
+                    if tries > MAX_RETRIES {
+                        return Err(Error::Timeout("waiting for the end of file ACK"));
+                    }
+                    s.send_binary_header(Header::pos(ZEOF, len))?;
+                }
+            }
+        }
+    }
+}
+
+pub fn receive(link: &mut Link, dir: &Path) -> Result<()> {
+    let mut s = Session::new(link);
+    let zrinit = Header::new(ZRINIT, [0, 0, 0, CANFDX | CANOVIO | CANFC32]);
+    s.send_hex_header(zrinit)?;
+    let mut file: Option<File> = None;
+    // name of the open file, as sent
+    let mut current = String::new();
+    let mut pos = 0u64;
+    let mut tries = 0;
+    loop {
+        let Some(h) = s.read_header()? else {
+            tries += 1;
+            if tries > MAX_RETRIES {
+                return Err(Error::Timeout("waiting for the sender"));
+            }
+            match file {
+                Some(_) => s.resume(pos)?,
+                None => s.send_hex_header(zrinit)?,
+            }
+            continue;
+        };
+        tries = 0;
+        match h.kind {
+            ZRQINIT => s.send_hex_header(zrinit)?,
+            ZSINIT => match s.read_subpacket()? {
+                Some(_) => s.send_hex_header(Header::pos(ZACK, 0))?,
+                None => s.send_hex_header(Header::pos(ZNAK, 0))?,
+            },
+            ZFILE => {
+                let Some((info, _)) = s.read_subpacket()? else {
+                    s.send_hex_header(Header::pos(ZNAK, 0))?;
+                    continue;
+                };
+                let mut fields = info.split(|&b| b == 0);
+                let name = String::from_utf8_lossy(fields.next().unwrap_or_default()).into_owned();
+                let size = fields
+                    .next()
+                    .and_then(|i| String::from_utf8_lossy(i).split(' ').next()?.parse().ok());
+                // the sender did not get our ZRPOS
+                if file.is_some() && name == current {
+                    s.send_hex_header(Header::pos(ZRPOS, pos))?;
+                    continue;
+                }
+                let path = transfer::receive_path(dir, &name);
+                match File::create(&path) {
+                    Ok(f) => {
+                        file = Some(f);
+                        current = name;
+                    }
+                    Err(e) => {
+                        s.send_hex_header(Header::pos(ZFERR, 0))?;
+                        return Err(e.into());
+                    }
+                }
+                pos = 0;
+                s.link
+                    .progress
+                    .start_file(&transfer::file_name(&path), size);
+                s.send_hex_header(Header::pos(ZRPOS, 0))?;
+            }
+            ZDATA => {
+                let Some(f) = &mut file else {
+                    s.send_hex_header(zrinit)?;
+                    continue;
+                };
+                if h.position() != pos {
+                    s.resume(pos)?;
+                    continue;
+                }
+                loop {
+                    let Some((data, end)) = s.read_subpacket()? else {
+                        s.resume(pos)?;
+                        break;
+                    };
+                    f.write_all(&data)?;
+                    pos += data.len() as u64;
+                    s.link.progress.bytes = pos;
+                    match end {
+                        ZCRCG => (),
+                        ZCRCQ => s.send_hex_header(Header::pos(ZACK, pos))?,
+                        ZCRCW => {
+                            s.send_hex_header(Header::pos(ZACK, pos))?;
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+            ZEOF if h.position() == pos => {
+                if let Some(mut f) = file.take() {
+                    f.flush()?;
+                    s.link.progress.end_file();
+                }
+                s.send_hex_header(zrinit)?;
+            }
+            // a ZEOF for another position went out before our last ZRPOS, the
+            // timeout asks for the data again if it is not coming
+            ZEOF => (),
+            ZFIN => {
+                s.send_hex_header(Header::pos(ZFIN, 0))?;
+                // "OO", over and out
+                let mut oo = 0;
+                while oo < 2 {
+                    match s.link.read(BYTE_TIMEOUT)? {
+                        Some(b'O') => oo += 1,
+                        Some(_) => (),
+                        None => break,
+                    }
+                }
+                return Ok(());
+            }
+            ZCAN | ZABORT => return Err(Error::RemoteCancelled),
+            _ => (),
+        }
+    }
+}